@@ -0,0 +1,38 @@
+//! Measures the per-call cost of `current_wrap_width`, which is meant to be
+//! cheap enough to call once per log line, against an uncached
+//! `terminal_size` query.
+//!
+//! Run with `cargo bench --bench wrap_width`. This uses a plain timing loop
+//! rather than the unstable `#[bench]` harness, so it works on stable Rust.
+
+extern crate terminal_size;
+
+use std::time::{Duration, Instant};
+
+use terminal_size::{current_wrap_width, terminal_size};
+
+/// Runs `f` `iters` times and returns the average time per call.
+fn time_per_call<F: FnMut() -> u64>(iters: u32, mut f: F) -> Duration {
+    let mut sink = 0u64;
+    let start = Instant::now();
+    for _ in 0..iters {
+        sink = sink.wrapping_add(f());
+    }
+    let elapsed = start.elapsed();
+    // Use the results so the loop isn't optimized away.
+    assert_ne!(sink, u64::MAX);
+    elapsed / iters
+}
+
+fn main() {
+    // Fill the cache first, so only the cached path is measured below.
+    current_wrap_width(80);
+
+    let cached = time_per_call(10_000_000, || u64::from(current_wrap_width(80)));
+    println!("current_wrap_width (cached): {:?} per call", cached);
+
+    let uncached = time_per_call(100_000, || {
+        terminal_size().map_or(0, |(w, _)| u64::from(w.0))
+    });
+    println!("terminal_size (uncached):    {:?} per call", uncached);
+}
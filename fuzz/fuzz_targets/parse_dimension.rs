@@ -0,0 +1,36 @@
+//! Feeds arbitrary bytes to the `COLUMNS`/`LINES` value parser and to the
+//! `Width`/`Height` parsers, checking that they never panic and only ever
+//! produce sane values.
+//!
+//! Run with `cargo fuzz run parse_dimension` from the repository root.
+
+#![no_main]
+
+use std::ffi::OsStr;
+use std::os::unix::ffi::OsStrExt;
+
+use libfuzzer_sys::fuzz_target;
+use terminal_size::fuzzing::parse_env_dimension;
+use terminal_size::{Height, Width};
+
+fuzz_target!(|data: &[u8]| {
+    // Environment values are arbitrary bytes, not necessarily UTF-8.
+    if let Some(n) = parse_env_dimension(OsStr::from_bytes(data)) {
+        assert_ne!(n, 0, "a dimension of 0 must be reported as unset");
+    }
+
+    // Whatever parses must print back to something that parses the same.
+    let s = String::from_utf8_lossy(data);
+    match s.parse::<Width>() {
+        Ok(w) => assert_eq!(w.to_string().parse::<Width>().ok(), Some(w)),
+        Err(e) => {
+            let _ = e.to_string();
+        }
+    }
+    match s.parse::<Height>() {
+        Ok(h) => assert_eq!(h.to_string().parse::<Height>().ok(), Some(h)),
+        Err(e) => {
+            let _ = e.to_string();
+        }
+    }
+});
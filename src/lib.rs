@@ -2,7 +2,7 @@
 //!
 //! Supports both Linux and Windows, but help is needed to test other platforms
 //!
-//! Tested on Rust Stable (1.4), Beta (1.5), and Nightly (1.6)
+//! Requires Rust 1.63 or newer.
 //!
 //! # Example
 //!
@@ -17,19 +17,829 @@
 //! }
 //! ```
 //!
+//! # Serde
+//!
+//! With the `serde` feature enabled, `Width` and `Height` serialize as bare
+//! integers (`80`), and `TerminalSize` as a labeled object
+//! (`{"width":80,"height":24,"pixel_width":0,"pixel_height":0}`, where the
+//! pixel fields may be left out when deserializing). For self-describing
+//! config files, the `serde_labeled` modules write a lone `Width` or
+//! `Height` as a labeled object too (`{"width":80}`).
+//!
 
-#[derive(Debug)]
+#[cfg(feature = "serde")]
+#[macro_use]
+extern crate serde;
+
+use std::env;
+use std::error::Error;
+use std::ffi::OsStr;
+use std::fmt;
+use std::num::ParseIntError;
+#[cfg(unix)]
+use std::os::unix::io::RawFd;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU32, Ordering};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize), serde(transparent))]
 pub struct Width(pub u16);
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize), serde(transparent))]
 pub struct Height(pub u16);
 
+impl Width {
+    /// A width of zero columns.
+    pub const ZERO: Width = Width(0);
+
+    /// Returns `true` if this width is zero columns.
+    pub fn is_zero(&self) -> bool {
+        self.0 == 0
+    }
+
+    /// Restricts this width to the range `min..=max`.
+    ///
+    /// Panics if `min > max`.
+    pub fn clamp(self, min: Width, max: Width) -> Width {
+        Ord::clamp(self, min, max)
+    }
+}
+
+impl Height {
+    /// A height of zero lines.
+    pub const ZERO: Height = Height(0);
+
+    /// Returns `true` if this height is zero lines.
+    pub fn is_zero(&self) -> bool {
+        self.0 == 0
+    }
+
+    /// Restricts this height to the range `min..=max`.
+    ///
+    /// Panics if `min > max`.
+    pub fn clamp(self, min: Height, max: Height) -> Height {
+        Ord::clamp(self, min, max)
+    }
+}
+
+/// The width and height of a terminal, bundled together.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct TerminalSize {
+    pub width: Width,
+    pub height: Height,
+    /// The width of the terminal in pixels, or 0 if unknown.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub pixel_width: u16,
+    /// The height of the terminal in pixels, or 0 if unknown.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub pixel_height: u16,
+}
+
+impl TerminalSize {
+    /// A size of zero columns by zero lines.
+    pub const ZERO: TerminalSize = TerminalSize {
+        width: Width::ZERO,
+        height: Height::ZERO,
+        pixel_width: 0,
+        pixel_height: 0,
+    };
+
+    /// Creates a size of `width` columns by `height` lines, with unknown
+    /// pixel dimensions.
+    ///
+    /// This is a `const fn`, so it can be used to build `const` and `static`
+    /// values:
+    ///
+    /// ```
+    /// use terminal_size::TerminalSize;
+    ///
+    /// static DEFAULT_LAYOUT: TerminalSize = TerminalSize::new(80, 24);
+    /// ```
+    pub const fn new(width: u16, height: u16) -> TerminalSize {
+        TerminalSize {
+            width: Width(width),
+            height: Height(height),
+            pixel_width: 0,
+            pixel_height: 0,
+        }
+    }
+
+    /// Encodes this size as 8 bytes for simple IPC.
+    ///
+    /// The layout is `width`, `height`, `pixel_width`, `pixel_height`, each
+    /// as a little-endian `u16`:
+    ///
+    /// ```text
+    /// bytes: 0..2   2..4    4..6         6..8
+    ///        width  height  pixel_width  pixel_height
+    /// ```
+    pub fn to_bytes(&self) -> [u8; 8] {
+        let mut bytes = [0; 8];
+        bytes[0..2].copy_from_slice(&self.width.0.to_le_bytes());
+        bytes[2..4].copy_from_slice(&self.height.0.to_le_bytes());
+        bytes[4..6].copy_from_slice(&self.pixel_width.to_le_bytes());
+        bytes[6..8].copy_from_slice(&self.pixel_height.to_le_bytes());
+        bytes
+    }
+
+    /// Decodes a size written by `to_bytes`.
+    pub fn from_bytes(bytes: [u8; 8]) -> TerminalSize {
+        TerminalSize {
+            width: Width(u16::from_le_bytes([bytes[0], bytes[1]])),
+            height: Height(u16::from_le_bytes([bytes[2], bytes[3]])),
+            pixel_width: u16::from_le_bytes([bytes[4], bytes[5]]),
+            pixel_height: u16::from_le_bytes([bytes[6], bytes[7]]),
+        }
+    }
+
+    /// Returns `true` if both dimensions are zero.
+    pub fn is_zero(&self) -> bool {
+        self.width.is_zero() && self.height.is_zero()
+    }
+
+    /// Returns `true` if either dimension is zero, so nothing can be drawn.
+    pub fn is_empty(&self) -> bool {
+        self.width.is_zero() || self.height.is_zero()
+    }
+
+    /// Returns a copy of this size with the width transformed by `f`.
+    pub fn map_width<F: FnOnce(Width) -> Width>(self, f: F) -> TerminalSize {
+        TerminalSize {
+            width: f(self.width),
+            ..self
+        }
+    }
+
+    /// Returns a copy of this size with the height transformed by `f`.
+    pub fn map_height<F: FnOnce(Height) -> Height>(self, f: F) -> TerminalSize {
+        TerminalSize {
+            height: f(self.height),
+            ..self
+        }
+    }
+
+    /// Restricts each dimension of this size to lie between the matching
+    /// dimensions of `min` and `max`.
+    ///
+    /// The pixel dimensions are kept as they are. Panics if either
+    /// dimension of `min` is greater than that of `max`.
+    pub fn clamp(self, min: TerminalSize, max: TerminalSize) -> TerminalSize {
+        TerminalSize {
+            width: self.width.clamp(min.width, max.width),
+            height: self.height.clamp(min.height, max.height),
+            ..self
+        }
+    }
+
+    /// Returns how many `(columns, lines)` were gained going from this size to
+    /// `other`, negative where `other` is smaller.
+    pub fn delta(&self, other: &TerminalSize) -> (i32, i32) {
+        (
+            i32::from(other.width.0) - i32::from(self.width.0),
+            i32::from(other.height.0) - i32::from(self.height.0),
+        )
+    }
+
+    /// Scales an image of `image` `(width, height)` pixels to the largest size
+    /// that fits on this terminal while keeping its aspect ratio, then rounds
+    /// each side down to whole cells.
+    ///
+    /// Returns the scaled `(width, height)` in pixels. The cell size is
+    /// worked out from the pixel and cell dimensions of this size, so this
+    /// returns `None` if the pixel dimensions are unknown, or if any
+    /// dimension (of the terminal or the image) is 0. On Unix,
+    /// `terminal_size_with_pixels` gives a size with the pixel dimensions
+    /// filled in.
+    pub fn scale_to_fit_pixels(&self, image: (u16, u16)) -> Option<(u16, u16)> {
+        let (image_w, image_h) = (u64::from(image.0), u64::from(image.1));
+        let cols = u64::from(self.width.0);
+        let rows = u64::from(self.height.0);
+        if cols == 0 || rows == 0 || image_w == 0 || image_h == 0 {
+            return None;
+        }
+        let cell_w = u64::from(self.pixel_width) / cols;
+        let cell_h = u64::from(self.pixel_height) / rows;
+        if cell_w == 0 || cell_h == 0 {
+            return None;
+        }
+
+        let (area_w, area_h) = (cols * cell_w, rows * cell_h);
+        let (w, h) = if image_h * area_w / image_w <= area_h {
+            (area_w, image_h * area_w / image_w)
+        } else {
+            (image_w * area_h / image_h, area_h)
+        };
+        Some(((w / cell_w * cell_w) as u16, (h / cell_h * cell_h) as u16))
+    }
+
+    /// Returns how many `(columns, rows)` of `item_width` x `item_height` cells
+    /// fit inside this size.
+    ///
+    /// Returns `(0, 0)` if either item dimension is 0.
+    pub fn grid_for(&self, item_width: u16, item_height: u16) -> (u16, u16) {
+        if item_width == 0 || item_height == 0 {
+            return (0, 0);
+        }
+        (self.width.0 / item_width, self.height.0 / item_height)
+    }
+}
+
+/// Which of the standard streams are attached to a terminal.
+///
+/// Returned by `stdio_tty_status`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StdioTty {
+    pub stdin: bool,
+    pub stdout: bool,
+    pub stderr: bool,
+}
+
+/// The error returned when a `Width` or `Height` can't be parsed from a string.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseSizeError(ParseIntError);
+
+impl fmt::Display for ParseSizeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "invalid terminal dimension: {}", self.0)
+    }
+}
+
+impl Error for ParseSizeError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        Some(&self.0)
+    }
+}
+
+/// Formats the number of columns, honouring width, fill and alignment.
+impl fmt::Display for Width {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+/// Formats the number of lines, honouring width, fill and alignment.
+impl fmt::Display for Height {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl From<Width> for f32 {
+    fn from(width: Width) -> f32 {
+        f32::from(width.0)
+    }
+}
+
+impl From<Width> for f64 {
+    fn from(width: Width) -> f64 {
+        f64::from(width.0)
+    }
+}
+
+impl From<Height> for f32 {
+    fn from(height: Height) -> f32 {
+        f32::from(height.0)
+    }
+}
+
+impl From<Height> for f64 {
+    fn from(height: Height) -> f64 {
+        f64::from(height.0)
+    }
+}
+
+/// Parses a bare number of columns, ignoring surrounding whitespace.
+impl FromStr for Width {
+    type Err = ParseSizeError;
+
+    fn from_str(s: &str) -> Result<Width, ParseSizeError> {
+        s.trim().parse().map(Width).map_err(ParseSizeError)
+    }
+}
+
+/// Parses a bare number of lines, ignoring surrounding whitespace.
+impl FromStr for Height {
+    type Err = ParseSizeError;
+
+    fn from_str(s: &str) -> Result<Height, ParseSizeError> {
+        s.trim().parse().map(Height).map_err(ParseSizeError)
+    }
+}
+
+/// The knobs that control how `terminal_size_with_config` finds the size.
+///
+/// `DetectConfig::default()` behaves exactly like `terminal_size`. On
+/// Windows there are no descriptors to pick: the console is found as
+/// `terminal_size` finds it, and `use_env` decides whether placeholder
+/// console sizes fall back to the environment.
+#[cfg(any(unix, windows))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DetectConfig<'a> {
+    /// The file descriptors to query, in order. The first tty that reports
+    /// a non-zero size wins. Defaults to just STDOUT.
+    #[cfg(unix)]
+    pub fds: &'a [RawFd],
+    /// Whether to fall back to the environment when the terminal doesn't
+    /// know its size. Defaults to `true`.
+    pub use_env: bool,
+    /// The variable holding the number of columns. Defaults to `COLUMNS`.
+    pub columns_var: &'a str,
+    /// The variable holding the number of lines. Defaults to `LINES`.
+    pub lines_var: &'a str,
+    /// A variable holding a `<columns>x<lines>` size (such as `120x40`) that
+    /// takes precedence over everything else when set to a valid value with
+    /// no side of 0. Defaults to `None`.
+    pub override_var: Option<&'a str>,
+    /// `(min, max)` bounds the result is clamped to. An axis where `min` is
+    /// greater than `max` is left unclamped. Defaults to `None`.
+    pub clamp: Option<(TerminalSize, TerminalSize)>,
+}
+
+#[cfg(any(unix, windows))]
+impl<'a> Default for DetectConfig<'a> {
+    fn default() -> DetectConfig<'a> {
+        DetectConfig {
+            #[cfg(unix)]
+            fds: unix::DEFAULT_FDS,
+            use_env: true,
+            columns_var: "COLUMNS",
+            lines_var: "LINES",
+            override_var: None,
+            clamp: None,
+        }
+    }
+}
+
+/// Returns the size of the terminal, found as described by `config`.
+///
+/// If `config.override_var` holds a valid size, that is used. Otherwise,
+/// on Unix, the descriptors in `config.fds` are queried in order. If none
+/// of them is a tty, returns `None`. If the ttys all report 0x0, the
+/// environment is used when `config.use_env` is set, and 0x0 is returned
+/// otherwise. On Windows, the console is queried as by `terminal_size`.
+#[cfg(any(unix, windows))]
+pub fn terminal_size_with_config(config: &DetectConfig) -> Option<(Width, Height)> {
+    let size = config
+        .override_var
+        .and_then(size_from_override)
+        .or_else(|| detect(config));
+    match (size, config.clamp) {
+        (Some((w, h)), Some((min, max))) => {
+            let w = if min.width <= max.width { w.clamp(min.width, max.width) } else { w };
+            let h = if min.height <= max.height { h.clamp(min.height, max.height) } else { h };
+            Some((w, h))
+        }
+        _ => size,
+    }
+}
+
+/// Parses the `<columns>x<lines>` value of the override variable `var`.
+///
+/// Like `COLUMNS` and `LINES`, a side of 0 makes the value invalid.
+fn size_from_override(var: &str) -> Option<(Width, Height)> {
+    let value = env::var_os(var)?.into_string().ok()?;
+    let (columns, lines) = value.split_once('x')?;
+    Some((
+        Width(parse_env_dimension(OsStr::new(columns))?),
+        Height(parse_env_dimension(OsStr::new(lines))?),
+    ))
+}
+
+/// Environment variable that makes `terminal_size` explain itself on stderr.
+#[cfg(any(unix, windows))]
+const DEBUG_ENV: &str = "TERMINAL_SIZE_DEBUG";
+
+/// Returns the size of the terminal using system env:
+/// COLUMNS and LINES
+///
+/// Only exported variables are visible here; shell-local ones never reach
+/// the process environment.
+///
+/// Values that aren't a valid number are treated as 0.
+/// If both env are 0, returns `None`. If only one of them is set, the other
+/// defaults to 80 columns or 24 lines, so the result is never half-empty.
+#[cfg(any(windows, test))]
+fn terminal_size_using_env() -> Option<(Width, Height)> {
+    terminal_size_using_env_vars("COLUMNS", "LINES")
+}
+
+/// Like `terminal_size_using_env`, but reads the number of columns and lines
+/// from the given variables.
+fn terminal_size_using_env_vars(columns: &str, lines: &str) -> Option<(Width, Height)> {
+    let c = env::var_os(columns)
+        .and_then(|v| parse_env_dimension(&v))
+        .map_or(Width(0), Width);
+    let r = env::var_os(lines)
+        .and_then(|v| parse_env_dimension(&v))
+        .map_or(Height(0), Height);
+
+    match (c, r) {
+        (Width(0), Height(0)) => None,
+        (Width(0), r) => Some((Width(DEFAULT_COLUMNS), r)),
+        (c, Height(0)) => Some((c, Height(DEFAULT_LINES))),
+        (c, r) => Some((c, r)),
+    }
+}
+
+/// Parses the value of a `COLUMNS`- or `LINES`-style variable.
+///
+/// Returns `None` for values that aren't valid UTF-8, aren't a number, or
+/// are 0.
+fn parse_env_dimension(value: &OsStr) -> Option<u16> {
+    match value.to_str()?.parse::<Width>() {
+        Ok(Width(0)) | Err(_) => None,
+        Ok(Width(n)) => Some(n),
+    }
+}
+
+/// The number of columns assumed when only `LINES` is set.
+const DEFAULT_COLUMNS: u16 = 80;
+/// The number of lines assumed when only `COLUMNS` is set.
+const DEFAULT_LINES: u16 = 24;
+
+/// The width cached by `current_wrap_width`, or one of the sentinels below.
+static WRAP_WIDTH: AtomicU32 = AtomicU32::new(WRAP_WIDTH_UNKNOWN);
+/// Nothing has been detected yet.
+const WRAP_WIDTH_UNKNOWN: u32 = u32::MAX;
+/// Detection ran, but found no usable width.
+const WRAP_WIDTH_NONE: u32 = u32::MAX - 1;
+
+/// Returns the terminal width to wrap log lines at, or `default` if it
+/// can't be detected.
+///
+/// This is meant to be called for every log line. The width is detected on
+/// the first call and cached, so later calls are a single atomic load. If
+/// `resize_channel` has been called, the watcher it starts keeps the cached
+/// width up to date; otherwise it stays at the width seen on the first call.
+pub fn current_wrap_width(default: u16) -> u16 {
+    let cached = match WRAP_WIDTH.load(Ordering::Relaxed) {
+        WRAP_WIDTH_UNKNOWN => store_wrap_width(terminal_size()),
+        cached => cached,
+    };
+    if cached == WRAP_WIDTH_NONE {
+        default
+    } else {
+        cached as u16
+    }
+}
+
+/// Caches the width of `size` for `current_wrap_width`, returning the value
+/// now in the cache.
+fn store_wrap_width(size: Option<(Width, Height)>) -> u32 {
+    let cached = match size {
+        Some((Width(w), _)) if w != 0 => u32::from(w),
+        _ => WRAP_WIDTH_NONE,
+    };
+    WRAP_WIDTH.store(cached, Ordering::Relaxed);
+    cached
+}
+
+/// Entry points for the fuzz targets in `fuzz/`, only built by cargo-fuzz.
+#[cfg(fuzzing)]
+#[doc(hidden)]
+pub mod fuzzing {
+    use std::ffi::OsStr;
+
+    /// See `parse_env_dimension`.
+    pub fn parse_env_dimension(value: &OsStr) -> Option<u16> {
+        super::parse_env_dimension(value)
+    }
+}
+
+#[cfg(feature = "serde")]
+pub mod serde_labeled;
+
+#[cfg(unix)]
+mod query;
+#[cfg(unix)]
+pub use query::terminal_size_interactive;
+
+#[cfg(unix)]
+mod resize;
+#[cfg(unix)]
+pub use resize::resize_channel;
+
 #[cfg(unix)]
 mod unix;
 #[cfg(unix)]
+use unix::detect;
+#[cfg(unix)]
+pub use unix::describe_environment;
+#[cfg(unix)]
+pub use unix::stdio_tty_status;
+#[cfg(unix)]
 pub use unix::terminal_size;
+#[cfg(unix)]
+pub use unix::terminal_size_of_owned;
+#[cfg(unix)]
 pub use unix::terminal_size_using_fd;
+#[cfg(unix)]
+pub use unix::terminal_size_with_pixels;
+#[cfg(target_os = "linux")]
+pub use unix::terminal_size_scan_proc_fds;
 
 #[cfg(windows)]
 mod windows;
 #[cfg(windows)]
+use windows::detect;
+#[cfg(windows)]
+pub use windows::describe_environment;
+#[cfg(windows)]
+pub use windows::stdio_tty_status;
+#[cfg(windows)]
 pub use windows::terminal_size;
+
+#[test]
+/// Map both dimensions of a size, one after the other
+fn map_width_and_height() {
+    let size = TerminalSize {
+        width: Width(80),
+        height: Height(24),
+        ..TerminalSize::ZERO
+    };
+    let mapped = size
+        .map_width(|Width(w)| Width(w - 2))
+        .map_height(|Height(h)| Height(h / 2));
+    assert_eq!(mapped.width, Width(78));
+    assert_eq!(mapped.height, Height(12));
+    assert_eq!(size.map_width(|w| w).height, Height(24));
+}
+
+#[test]
+/// Parse bare numbers into `Width` and `Height`
+fn parse_width_and_height() {
+    assert_eq!("80".parse::<Width>(), Ok(Width(80)));
+    assert_eq!(" 24\n".parse::<Height>(), Ok(Height(24)));
+    assert_eq!("65535".parse::<Width>(), Ok(Width(65535)));
+
+    assert!("".parse::<Width>().is_err());
+    assert!("   ".parse::<Height>().is_err());
+    assert!("65536".parse::<Width>().is_err());
+    assert!("-1".parse::<Height>().is_err());
+    assert!("80x24".parse::<Width>().is_err());
+
+    let err = "".parse::<Width>().unwrap_err();
+    assert!(err.to_string().starts_with("invalid terminal dimension"));
+}
+
+#[test]
+/// Fit a grid of equal cells into a size
+fn grid_for_fits_whole_cells() {
+    let size = TerminalSize {
+        width: Width(80),
+        height: Height(24),
+        ..TerminalSize::ZERO
+    };
+    assert_eq!(size.grid_for(20, 6), (4, 4));
+    assert_eq!(size.grid_for(30, 5), (2, 4));
+    assert_eq!(size.grid_for(80, 24), (1, 1));
+    assert_eq!(size.grid_for(81, 24), (0, 1));
+    assert_eq!(size.grid_for(0, 6), (0, 0));
+    assert_eq!(size.grid_for(20, 0), (0, 0));
+}
+
+#[cfg(feature = "serde")]
+#[test]
+/// Round-trip the default serde forms through JSON
+fn serde_default_forms() {
+    extern crate serde_json;
+
+    let w: Width = serde_json::from_str("80").unwrap();
+    assert_eq!(w, Width(80));
+    assert_eq!(serde_json::to_string(&Height(24)).unwrap(), "24");
+
+    let size = TerminalSize {
+        width: Width(80),
+        height: Height(24),
+        ..TerminalSize::ZERO
+    };
+    let json = serde_json::to_string(&size).unwrap();
+    assert_eq!(
+        json,
+        r#"{"width":80,"height":24,"pixel_width":0,"pixel_height":0}"#
+    );
+    assert_eq!(serde_json::from_str::<TerminalSize>(&json).unwrap(), size);
+    let short: TerminalSize = serde_json::from_str(r#"{"width":80,"height":24}"#).unwrap();
+    assert_eq!(short, size);
+}
+
+#[test]
+/// Check the zero predicates on each type
+fn zero_predicates() {
+    assert!(Width::ZERO.is_zero());
+    assert!(!Width(1).is_zero());
+    assert!(Height::ZERO.is_zero());
+    assert!(!Height(1).is_zero());
+
+    assert!(TerminalSize::ZERO.is_zero());
+    assert!(TerminalSize::ZERO.is_empty());
+
+    let only_width = TerminalSize {
+        width: Width(80),
+        height: Height::ZERO,
+        ..TerminalSize::ZERO
+    };
+    assert!(!only_width.is_zero());
+    assert!(only_width.is_empty());
+
+    let only_height = TerminalSize {
+        width: Width::ZERO,
+        height: Height(24),
+        ..TerminalSize::ZERO
+    };
+    assert!(!only_height.is_zero());
+    assert!(only_height.is_empty());
+
+    let full = TerminalSize {
+        width: Width(80),
+        height: Height(24),
+        ..TerminalSize::ZERO
+    };
+    assert!(!full.is_zero());
+    assert!(!full.is_empty());
+}
+
+#[test]
+/// Round-trip a size through its byte encoding
+fn bytes_round_trip() {
+    let size = TerminalSize {
+        width: Width(80),
+        height: Height(24),
+        pixel_width: 640,
+        pixel_height: 0x1234,
+    };
+    let bytes = size.to_bytes();
+    assert_eq!(bytes, [80, 0, 24, 0, 0x80, 0x02, 0x34, 0x12]);
+    assert_eq!(TerminalSize::from_bytes(bytes), size);
+    assert_eq!(TerminalSize::from_bytes([0; 8]), TerminalSize::ZERO);
+}
+
+#[test]
+/// Build sizes at compile time with `TerminalSize::new`
+fn new_is_const() {
+    const SMALL: TerminalSize = TerminalSize::new(40, 12);
+    static LAYOUTS: [TerminalSize; 2] = [SMALL, TerminalSize::new(80, 24)];
+
+    assert_eq!(SMALL.width, Width(40));
+    assert_eq!(SMALL.height, Height(12));
+    assert_eq!(SMALL.pixel_width, 0);
+    assert_eq!(SMALL.pixel_height, 0);
+    assert_eq!(LAYOUTS[1].grid_for(40, 12), (2, 2));
+}
+
+#[test]
+/// Clamp dimensions at and beyond each bound
+fn clamp_dimensions() {
+    assert_eq!(Width(20).clamp(Width(40), Width(200)), Width(40));
+    assert_eq!(Width(40).clamp(Width(40), Width(200)), Width(40));
+    assert_eq!(Width(100).clamp(Width(40), Width(200)), Width(100));
+    assert_eq!(Width(200).clamp(Width(40), Width(200)), Width(200));
+    assert_eq!(Width(300).clamp(Width(40), Width(200)), Width(200));
+
+    assert_eq!(Height(5).clamp(Height(10), Height(50)), Height(10));
+    assert_eq!(Height(10).clamp(Height(10), Height(50)), Height(10));
+    assert_eq!(Height(50).clamp(Height(10), Height(50)), Height(50));
+    assert_eq!(Height(60).clamp(Height(10), Height(50)), Height(50));
+
+    let min = TerminalSize::new(40, 10);
+    let max = TerminalSize::new(200, 50);
+    assert_eq!(TerminalSize::new(20, 60).clamp(min, max), TerminalSize::new(40, 50));
+    assert_eq!(TerminalSize::new(300, 5).clamp(min, max), TerminalSize::new(200, 10));
+    assert_eq!(TerminalSize::new(80, 24).clamp(min, max), TerminalSize::new(80, 24));
+}
+
+#[test]
+/// Measure the change between two sizes
+fn delta_between_sizes() {
+    let size = TerminalSize::new(80, 24);
+    assert_eq!(size.delta(&TerminalSize::new(100, 30)), (20, 6));
+    assert_eq!(size.delta(&TerminalSize::new(60, 10)), (-20, -14));
+    assert_eq!(size.delta(&TerminalSize::new(100, 10)), (20, -14));
+    assert_eq!(size.delta(&size), (0, 0));
+    assert_eq!(
+        TerminalSize::new(u16::MAX, u16::MAX).delta(&TerminalSize::ZERO),
+        (-65535, -65535)
+    );
+}
+
+#[test]
+/// Scale images to fit the terminal, snapped to 10x20 pixel cells
+fn scale_to_fit_pixels_snaps_to_cells() {
+    let size = TerminalSize {
+        pixel_width: 800,
+        pixel_height: 480,
+        ..TerminalSize::new(80, 24)
+    };
+    // Oversized, limited by width.
+    assert_eq!(size.scale_to_fit_pixels((1600, 800)), Some((800, 400)));
+    // Oversized, limited by height.
+    assert_eq!(size.scale_to_fit_pixels((1000, 2000)), Some((240, 480)));
+    // Undersized images are scaled up.
+    assert_eq!(size.scale_to_fit_pixels((100, 100)), Some((480, 480)));
+    // Heights that land between cell boundaries are rounded down.
+    assert_eq!(size.scale_to_fit_pixels((333, 100)), Some((800, 240)));
+    // Exact fit.
+    assert_eq!(size.scale_to_fit_pixels((800, 480)), Some((800, 480)));
+
+    assert_eq!(size.scale_to_fit_pixels((0, 100)), None);
+    assert_eq!(TerminalSize::new(80, 24).scale_to_fit_pixels((100, 100)), None);
+}
+
+#[test]
+/// `Display` forwards the format spec to the inner number
+fn display_honours_format_spec() {
+    assert_eq!(format!("{}", Width(80)), "80");
+    assert_eq!(format!("{:>4}", Width(8)), "   8");
+    assert_eq!(format!("{:<4}|", Height(8)), "8   |");
+    assert_eq!(format!("{:*^5}", Height(24)), "*24**");
+    assert_eq!(format!("{:04}", Width(7)), "0007");
+}
+
+#[test]
+/// The wrap width is detected once, then served from the cache
+fn current_wrap_width_is_cached() {
+    let detected = current_wrap_width(77);
+    match terminal_size() {
+        Some((Width(w), _)) if w != 0 => assert_eq!(detected, w),
+        _ => {
+            assert_eq!(detected, 77);
+            assert_eq!(current_wrap_width(99), 99);
+        }
+    }
+
+    store_wrap_width(Some((Width(123), Height(45))));
+    assert_eq!(current_wrap_width(77), 123);
+    store_wrap_width(Some((Width(0), Height(45))));
+    assert_eq!(current_wrap_width(77), 77);
+    store_wrap_width(None);
+    assert_eq!(current_wrap_width(80), 80);
+}
+
+#[test]
+/// A lone `COLUMNS` or `LINES` is completed with the usual 80x24 default
+fn env_fills_in_missing_dimension() {
+    env::set_var("TERMINAL_SIZE_TEST_ONLY_COLS", "132");
+    env::set_var("TERMINAL_SIZE_TEST_ONLY_LINES", "50");
+    env::set_var("TERMINAL_SIZE_TEST_ZERO", "0");
+    let unset = "TERMINAL_SIZE_TEST_UNSET";
+
+    assert_eq!(
+        terminal_size_using_env_vars("TERMINAL_SIZE_TEST_ONLY_COLS", unset),
+        Some((Width(132), Height(24)))
+    );
+    assert_eq!(
+        terminal_size_using_env_vars(unset, "TERMINAL_SIZE_TEST_ONLY_LINES"),
+        Some((Width(80), Height(50)))
+    );
+    assert_eq!(
+        terminal_size_using_env_vars("TERMINAL_SIZE_TEST_ONLY_COLS", "TERMINAL_SIZE_TEST_ZERO"),
+        Some((Width(132), Height(24)))
+    );
+    assert_eq!(
+        terminal_size_using_env_vars("TERMINAL_SIZE_TEST_ONLY_COLS", "TERMINAL_SIZE_TEST_ONLY_LINES"),
+        Some((Width(132), Height(50)))
+    );
+    assert_eq!(terminal_size_using_env_vars(unset, unset), None);
+    assert_eq!(terminal_size_using_env_vars("TERMINAL_SIZE_TEST_ZERO", unset), None);
+}
+
+#[test]
+/// Dimensions convert to floats exactly
+fn dimensions_into_floats() {
+    assert_eq!(f32::from(Width(80)), 80.0);
+    assert_eq!(f64::from(Width(u16::MAX)), 65535.0);
+    assert_eq!(f32::from(Height(24)), 24.0);
+    assert_eq!(f64::from(Height(0)), 0.0);
+    assert_eq!(20.0 / f32::from(Width(80)), 0.25);
+}
+
+#[test]
+#[cfg(unix)]
+/// Environment values parse like `Width`, with 0 and non-UTF-8 meaning unset
+fn parse_env_dimension_values() {
+    use std::os::unix::ffi::OsStrExt;
+
+    assert_eq!(parse_env_dimension(OsStr::new(" 132\n")), Some(132));
+    assert_eq!(parse_env_dimension(OsStr::new("0")), None);
+    assert_eq!(parse_env_dimension(OsStr::new("wide")), None);
+    assert_eq!(parse_env_dimension(OsStr::from_bytes(b"1\xff")), None);
+}
+
+#[test]
+#[cfg(any(unix, windows))]
+/// The override variable and clamp bounds work without any terminal
+fn detect_config_override_and_clamp() {
+    env::set_var("TERMINAL_SIZE_TEST_CONFIG_OVERRIDE", "300x5");
+    let config = DetectConfig {
+        override_var: Some("TERMINAL_SIZE_TEST_CONFIG_OVERRIDE"),
+        ..DetectConfig::default()
+    };
+    assert_eq!(terminal_size_with_config(&config), Some((Width(300), Height(5))));
+
+    let config = DetectConfig {
+        clamp: Some((TerminalSize::new(40, 10), TerminalSize::new(200, 50))),
+        ..config
+    };
+    assert_eq!(terminal_size_with_config(&config), Some((Width(200), Height(10))));
+}
@@ -0,0 +1,203 @@
+//! Asking the terminal for its size with escape sequences.
+//!
+//! This is the last resort for terminals that don't support `TIOCGWINSZ`:
+//! `/dev/tty` is put in raw mode, the xterm window-manipulation reports
+//! `CSI 14 t` (size in pixels) and `CSI 18 t` (size in cells) are sent, and
+//! the replies are read back.
+
+extern crate libc;
+
+use std::fs::{File, OpenOptions};
+use std::io::{self, Write};
+use std::mem;
+use std::os::raw::*;
+use std::os::unix::fs::OpenOptionsExt;
+use std::os::unix::io::AsRawFd;
+use std::time::{Duration, Instant};
+
+use super::{Height, TerminalSize, Width};
+
+/// How long the terminal must stay quiet after a timeout before we give up
+/// on a late reply.
+const LATE_REPLY_QUIET: Duration = Duration::from_millis(100);
+
+/// The longest we keep discarding late replies after a timeout.
+const LATE_REPLY_MAX: Duration = Duration::from_millis(500);
+
+/// Asks the terminal behind `/dev/tty` for its size in cells and pixels.
+///
+/// This writes `CSI 14 t` and `CSI 18 t` to the terminal and waits for the
+/// replies, blocking for up to `timeout`. The terminal is in raw mode while
+/// waiting and its previous settings are restored before returning, even
+/// if this panics. Since the replies arrive on the terminal's input, don't
+/// call this while another thread is reading from it.
+///
+/// Returns `None` if there's no `/dev/tty`, or if the terminal doesn't
+/// report its size in cells within `timeout`. Terminals that report cells
+/// but not pixels give a size with `pixel_width` and `pixel_height` of 0.
+///
+/// After a timeout, replies that are only a little late are read and
+/// discarded until the terminal has been quiet for 100ms, so that they
+/// don't show up as typed input. This can add up to half a second.
+pub fn terminal_size_interactive(timeout: Duration) -> Option<TerminalSize> {
+    let deadline = Instant::now() + timeout;
+    let mut tty = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .custom_flags(libc::O_NOCTTY)
+        .open("/dev/tty")
+        .ok()?;
+    let mut raw = RawMode::enable(&tty).ok()?;
+
+    tty.write_all(b"\x1b[14t\x1b[18t").ok()?;
+    tty.flush().ok()?;
+
+    let mut replies = Vec::new();
+    let mut buf = [0u8; 64];
+    // Terminals answer in order, so once the cell report is in, any pixel
+    // report has arrived too.
+    while find_report(&replies, 8).is_none() {
+        if !wait_readable(&tty, deadline) {
+            discard_late_replies(&tty);
+            return None;
+        }
+        let n = unsafe { libc::read(tty.as_raw_fd(), buf.as_mut_ptr() as *mut c_void, buf.len()) };
+        if n <= 0 {
+            return None;
+        }
+        replies.extend_from_slice(&buf[..n as usize]);
+    }
+
+    let (rows, cols) = find_report(&replies, 8)?;
+    let (pixel_height, pixel_width) = find_report(&replies, 4).unwrap_or((0, 0));
+    raw.answered = true;
+    Some(TerminalSize {
+        width: Width(cols),
+        height: Height(rows),
+        pixel_width,
+        pixel_height,
+    })
+}
+
+/// Puts a terminal in raw mode, restoring its previous settings when dropped.
+///
+/// Unless `answered` was set, pending input is discarded on restore, since
+/// it may hold part of a reply. The guard must be dropped before the file it
+/// was created from is closed.
+struct RawMode {
+    fd: c_int,
+    saved: libc::termios,
+    answered: bool,
+}
+
+impl RawMode {
+    fn enable(tty: &File) -> io::Result<RawMode> {
+        let fd = tty.as_raw_fd();
+        unsafe {
+            let mut saved: libc::termios = mem::zeroed();
+            if libc::tcgetattr(fd, &mut saved) != 0 {
+                return Err(io::Error::last_os_error());
+            }
+            let mut raw = saved;
+            libc::cfmakeraw(&mut raw);
+            if libc::tcsetattr(fd, libc::TCSANOW, &raw) != 0 {
+                return Err(io::Error::last_os_error());
+            }
+            Ok(RawMode {
+                fd,
+                saved,
+                answered: false,
+            })
+        }
+    }
+}
+
+impl Drop for RawMode {
+    fn drop(&mut self) {
+        let when = if self.answered { libc::TCSANOW } else { libc::TCSAFLUSH };
+        unsafe {
+            libc::tcsetattr(self.fd, when, &self.saved);
+        }
+    }
+}
+
+/// Waits until `tty` has input, returning `false` if `deadline` passes first.
+fn wait_readable(tty: &File, deadline: Instant) -> bool {
+    loop {
+        let now = Instant::now();
+        if now >= deadline {
+            return false;
+        }
+        // Round up so we never spin on a timeout of 0ms.
+        let remaining = deadline - now + Duration::from_micros(999);
+        let millis = remaining.as_millis().min(c_int::MAX as u128) as c_int;
+        let mut pollfd = libc::pollfd {
+            fd: tty.as_raw_fd(),
+            events: libc::POLLIN,
+            revents: 0,
+        };
+        match unsafe { libc::poll(&mut pollfd, 1, millis) } {
+            n if n > 0 => return true,
+            0 => return false,
+            _ if io::Error::last_os_error().kind() == io::ErrorKind::Interrupted => continue,
+            _ => return false,
+        }
+    }
+}
+
+/// Reads and throws away input until `tty` has been quiet for
+/// `LATE_REPLY_QUIET`, or `LATE_REPLY_MAX` has passed.
+fn discard_late_replies(tty: &File) {
+    let give_up = Instant::now() + LATE_REPLY_MAX;
+    let mut buf = [0u8; 64];
+    while wait_readable(tty, (Instant::now() + LATE_REPLY_QUIET).min(give_up)) {
+        let n = unsafe { libc::read(tty.as_raw_fd(), buf.as_mut_ptr() as *mut c_void, buf.len()) };
+        if n <= 0 {
+            return;
+        }
+    }
+}
+
+/// Finds the report `CSI <kind> ; <a> ; <b> t` in `input` and returns
+/// `(a, b)`.
+fn find_report(input: &[u8], kind: u16) -> Option<(u16, u16)> {
+    let mut rest = input;
+    while let Some(start) = rest.windows(2).position(|w| w == b"\x1b[") {
+        rest = &rest[start + 2..];
+        let end = rest.iter().position(|&b| !(b.is_ascii_digit() || b == b';'))?;
+        if rest[end] != b't' {
+            continue;
+        }
+        let params = match ::std::str::from_utf8(&rest[..end]) {
+            Ok(params) => params,
+            Err(_) => continue,
+        };
+        let mut fields = params.split(';').map(|f| f.parse::<u16>().ok());
+        if let (Some(Some(k)), Some(Some(a)), Some(Some(b)), None) =
+            (fields.next(), fields.next(), fields.next(), fields.next())
+        {
+            if k == kind {
+                return Some((a, b));
+            }
+        }
+    }
+    None
+}
+
+#[test]
+/// Pick the cell and pixel reports out of a stream of replies
+fn find_report_parses_replies() {
+    let replies = b"\x1b[4;480;640t\x1b[8;24;80t";
+    assert_eq!(find_report(replies, 4), Some((480, 640)));
+    assert_eq!(find_report(replies, 8), Some((24, 80)));
+    assert_eq!(find_report(replies, 6), None);
+
+    // Unrelated sequences and stray input are skipped.
+    let noisy = b"abc\x1b[?1;2c\x1b[1;2R\x1b[8;50;132t";
+    assert_eq!(find_report(noisy, 8), Some((50, 132)));
+
+    // Incomplete reports aren't matched yet.
+    assert_eq!(find_report(b"\x1b[8;24;8", 8), None);
+    assert_eq!(find_report(b"\x1b[8;24t", 8), None);
+    assert_eq!(find_report(b"\x1b[8;24;99999t", 8), None);
+}
@@ -0,0 +1,227 @@
+//! Watching for terminal resizes.
+//!
+//! The first call to `resize_channel` installs a `SIGWINCH` handler that
+//! writes a byte to a pipe (the "self-pipe trick"), and spawns a thread that
+//! reads the pipe, queries the new size and forwards it to every channel.
+//! The watcher also keeps the width cached by `current_wrap_width` current.
+
+extern crate libc;
+
+use std::io;
+use std::mem;
+use std::ptr;
+use std::os::raw::*;
+use std::sync::atomic::{AtomicI32, AtomicUsize, Ordering};
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::sync::{Mutex, Once};
+use std::thread;
+
+use super::{store_wrap_width, terminal_size, Height, Width};
+
+/// Write end of the self-pipe, or -1 until the watcher is running.
+static PIPE_WRITE: AtomicI32 = AtomicI32::new(-1);
+
+/// The `SIGWINCH` handler that was installed before ours, chained to from
+/// `on_sigwinch`.
+static PREV_HANDLER: AtomicUsize = AtomicUsize::new(0);
+static PREV_FLAGS: AtomicUsize = AtomicUsize::new(0);
+
+static START: Once = Once::new();
+static SUBSCRIBERS: Mutex<Vec<Sender<(Width, Height)>>> = Mutex::new(Vec::new());
+
+/// Returns a channel that receives the new terminal size after each resize.
+///
+/// Resizes that arrive in quick succession are coalesced into a single
+/// event, and resizes for which `terminal_size` returns `None` are skipped.
+/// Any `SIGWINCH` handler installed before the first call keeps being
+/// called.
+///
+/// Dropping the receiver closes the channel; the watcher forgets it when
+/// the next resize is delivered. If the watcher can't be started, the
+/// returned channel is already disconnected.
+pub fn resize_channel() -> Receiver<(Width, Height)> {
+    START.call_once(|| {
+        if let Some((read_fd, prev)) = install() {
+            let spawned = thread::Builder::new()
+                .name("terminal_size-resize".into())
+                .spawn(move || watch(read_fd));
+            if spawned.is_err() {
+                uninstall(read_fd, &prev);
+            }
+        }
+    });
+
+    let (tx, rx) = channel();
+    if PIPE_WRITE.load(Ordering::SeqCst) >= 0 {
+        SUBSCRIBERS
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .push(tx);
+    }
+    rx
+}
+
+/// Creates the self-pipe and installs the `SIGWINCH` handler, returning the
+/// read end of the pipe and the action that was installed before.
+fn install() -> Option<(c_int, libc::sigaction)> {
+    let mut fds: [c_int; 2] = [-1; 2];
+    unsafe {
+        if libc::pipe(fds.as_mut_ptr()) != 0 {
+            return None;
+        }
+        for &fd in &fds {
+            libc::fcntl(fd, libc::F_SETFD, libc::FD_CLOEXEC);
+        }
+        // The handler must never block, even if the watcher falls behind.
+        let flags = libc::fcntl(fds[1], libc::F_GETFL);
+        libc::fcntl(fds[1], libc::F_SETFL, flags | libc::O_NONBLOCK);
+    }
+    PIPE_WRITE.store(fds[1], Ordering::SeqCst);
+
+    unsafe {
+        // Record the previous handler before ours can run and chain to it.
+        let mut prev: libc::sigaction = mem::zeroed();
+        if libc::sigaction(libc::SIGWINCH, ptr::null(), &mut prev) != 0 {
+            close_pipe(fds[0]);
+            return None;
+        }
+        PREV_FLAGS.store(prev.sa_flags as usize, Ordering::SeqCst);
+        PREV_HANDLER.store(prev.sa_sigaction as usize, Ordering::SeqCst);
+
+        let mut action: libc::sigaction = mem::zeroed();
+        action.sa_sigaction = on_sigwinch as *const () as libc::sighandler_t;
+        action.sa_flags = libc::SA_SIGINFO | libc::SA_RESTART;
+        libc::sigemptyset(&mut action.sa_mask);
+        if libc::sigaction(libc::SIGWINCH, &action, ptr::null_mut()) != 0 {
+            close_pipe(fds[0]);
+            return None;
+        }
+        Some((fds[0], prev))
+    }
+}
+
+/// Puts back the `SIGWINCH` action `prev` and closes the self-pipe, undoing
+/// `install`.
+fn uninstall(read_fd: c_int, prev: &libc::sigaction) {
+    unsafe {
+        libc::sigaction(libc::SIGWINCH, prev, ptr::null_mut());
+    }
+    close_pipe(read_fd);
+}
+
+/// Closes both ends of the self-pipe, marking the watcher as not running.
+fn close_pipe(read_fd: c_int) {
+    let write_fd = PIPE_WRITE.swap(-1, Ordering::SeqCst);
+    unsafe {
+        libc::close(read_fd);
+        if write_fd >= 0 {
+            libc::close(write_fd);
+        }
+    }
+}
+
+extern "C" fn on_sigwinch(signum: c_int, info: *mut libc::siginfo_t, context: *mut c_void) {
+    let errno = errno_location();
+    let saved_errno = errno.map(|p| unsafe { *p });
+
+    let fd = PIPE_WRITE.load(Ordering::Relaxed);
+    if fd >= 0 {
+        let byte = 0u8;
+        unsafe {
+            libc::write(fd, &byte as *const u8 as *const c_void, 1);
+        }
+    }
+
+    let prev = PREV_HANDLER.load(Ordering::Relaxed);
+    if prev != libc::SIG_DFL && prev != libc::SIG_IGN {
+        unsafe {
+            if PREV_FLAGS.load(Ordering::Relaxed) & libc::SA_SIGINFO as usize != 0 {
+                let handler: extern "C" fn(c_int, *mut libc::siginfo_t, *mut c_void) =
+                    mem::transmute(prev);
+                handler(signum, info, context);
+            } else {
+                let handler: extern "C" fn(c_int) = mem::transmute(prev);
+                handler(signum);
+            }
+        }
+    }
+
+    if let (Some(p), Some(value)) = (errno, saved_errno) {
+        unsafe {
+            *p = value;
+        }
+    }
+}
+
+/// Reads the self-pipe forever, forwarding the new size after each wakeup.
+fn watch(read_fd: c_int) {
+    // Reading up to a buffer's worth of bytes at once coalesces bursts of
+    // signals into a single event.
+    let mut buf = [0u8; 64];
+    loop {
+        let n = unsafe { libc::read(read_fd, buf.as_mut_ptr() as *mut c_void, buf.len()) };
+        if n < 0 {
+            if io::Error::last_os_error().raw_os_error() == Some(libc::EINTR) {
+                continue;
+            }
+            return;
+        }
+        if n == 0 {
+            return;
+        }
+
+        let size = terminal_size();
+        store_wrap_width(size);
+        if let Some(size) = size {
+            SUBSCRIBERS
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .retain(|tx| tx.send(size).is_ok());
+        }
+    }
+}
+
+/// Returns a pointer to this thread's `errno`, so the signal handler can
+/// leave it as it found it, or `None` on platforms where we don't know how.
+#[cfg(any(
+    target_os = "linux",
+    target_os = "emscripten",
+    target_os = "hurd",
+    target_os = "dragonfly"
+))]
+fn errno_location() -> Option<*mut c_int> {
+    Some(unsafe { libc::__errno_location() })
+}
+#[cfg(any(
+    target_os = "android",
+    target_os = "netbsd",
+    target_os = "openbsd",
+    target_os = "cygwin"
+))]
+fn errno_location() -> Option<*mut c_int> {
+    Some(unsafe { libc::__errno() })
+}
+#[cfg(any(
+    target_os = "macos",
+    target_os = "ios",
+    target_os = "freebsd"
+))]
+fn errno_location() -> Option<*mut c_int> {
+    Some(unsafe { libc::__error() })
+}
+#[cfg(not(any(
+    target_os = "linux",
+    target_os = "emscripten",
+    target_os = "hurd",
+    target_os = "android",
+    target_os = "netbsd",
+    target_os = "openbsd",
+    target_os = "cygwin",
+    target_os = "macos",
+    target_os = "ios",
+    target_os = "freebsd",
+    target_os = "dragonfly"
+)))]
+fn errno_location() -> Option<*mut c_int> {
+    None
+}
@@ -0,0 +1,92 @@
+//! Serde helpers that write a lone `Width` or `Height` as a labeled object.
+//!
+//! By default `Width(80)` serializes as `80`. Annotating a field with
+//! `#[serde(with = "terminal_size::serde_labeled::width")]` writes it as
+//! `{"width":80}` instead, which reads better in hand-edited config files.
+//!
+//! ```
+//! # extern crate serde_json;
+//! # #[macro_use] extern crate serde;
+//! # extern crate terminal_size;
+//! use terminal_size::{Height, Width};
+//!
+//! #[derive(Serialize, Deserialize)]
+//! struct Config {
+//!     #[serde(with = "terminal_size::serde_labeled::width")]
+//!     min_width: Width,
+//!     #[serde(with = "terminal_size::serde_labeled::height")]
+//!     min_height: Height,
+//! }
+//!
+//! # fn main() {
+//! let config = Config { min_width: Width(80), min_height: Height(24) };
+//! assert_eq!(
+//!     serde_json::to_string(&config).unwrap(),
+//!     r#"{"min_width":{"width":80},"min_height":{"height":24}}"#
+//! );
+//! # }
+//! ```
+
+/// Serializes a `Width` as `{"width": n}`.
+pub mod width {
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    use super::super::Width;
+
+    #[derive(Serialize, Deserialize)]
+    struct Labeled {
+        width: u16,
+    }
+
+    pub fn serialize<S: Serializer>(width: &Width, serializer: S) -> Result<S::Ok, S::Error> {
+        Labeled { width: width.0 }.serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Width, D::Error> {
+        Labeled::deserialize(deserializer).map(|l| Width(l.width))
+    }
+}
+
+/// Serializes a `Height` as `{"height": n}`.
+pub mod height {
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    use super::super::Height;
+
+    #[derive(Serialize, Deserialize)]
+    struct Labeled {
+        height: u16,
+    }
+
+    pub fn serialize<S: Serializer>(height: &Height, serializer: S) -> Result<S::Ok, S::Error> {
+        Labeled { height: height.0 }.serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Height, D::Error> {
+        Labeled::deserialize(deserializer).map(|l| Height(l.height))
+    }
+}
+
+#[test]
+/// Round-trip the labeled forms through JSON
+fn labeled_round_trip() {
+    extern crate serde_json;
+
+    use super::{Height, Width};
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Config {
+        #[serde(with = "width")]
+        w: Width,
+        #[serde(with = "height")]
+        h: Height,
+    }
+
+    let config = Config {
+        w: Width(120),
+        h: Height(40),
+    };
+    let json = serde_json::to_string(&config).unwrap();
+    assert_eq!(json, r#"{"w":{"width":120},"h":{"height":40}}"#);
+    assert_eq!(serde_json::from_str::<Config>(&json).unwrap(), config);
+}
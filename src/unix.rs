@@ -1,14 +1,23 @@
 extern crate libc;
 use std::os::raw::*;
+use std::os::unix::io::{AsRawFd, OwnedFd, RawFd};
 use std::env;
+use std::ffi::OsStr;
+#[cfg(target_os = "linux")]
+use std::fs;
 
-use super::{Height, Width};
+use super::{
+    terminal_size_using_env_vars, terminal_size_with_config, DetectConfig, Height, StdioTty, TerminalSize,
+    Width, DEBUG_ENV,
+};
+#[cfg(test)]
+use super::terminal_size_using_env;
 
 #[cfg(target_os = "macos")]
 const TIOCGWINSZ: c_ulong = 0x40087468;
-#[cfg(all(target_env = "musl", not(target_os = "macos")))]
+#[cfg(all(target_env = "musl", not(target_os = "macos"), not(target_os = "nto")))]
 const TIOCGWINSZ: c_int = 0x00005413;
-#[cfg(all(not(target_env = "musl"), not(target_os = "macos")))]
+#[cfg(all(not(target_env = "musl"), not(target_os = "macos"), not(target_os = "nto")))]
 const TIOCGWINSZ: c_ulong = 0x00005413;
 
 #[derive(Debug)]
@@ -19,83 +28,313 @@ struct WinSize {
     ws_ypixel: c_ushort,
 }
 
+/// Describes how this backend reads the terminal size.
+#[cfg(not(target_os = "nto"))]
+const BACKEND: &str = "unix (ioctl TIOCGWINSZ)";
+#[cfg(target_os = "nto")]
+const BACKEND: &str = "qnx (tcgetsize)";
+
 /// Returns the size of the terminal defaulting to STDOUT, if available.
 ///
 /// If STDOUT is not a tty, returns `None`
 /// If STDOUT is a tty, but both width and height is 0,
 /// fallback to use system env: COLUMNS and LINES.
+/// If only one of them is set, the other defaults to 80 columns or 24 lines.
+///
+/// Note that most shells keep `COLUMNS` and `LINES` as shell variables that
+/// are not exported, so child processes usually can't see them. Only values
+/// that were exported into this process's environment (e.g. with
+/// `export COLUMNS LINES`) are used, and when present they are trusted as-is.
+///
+/// If `TERMINAL_SIZE_DEBUG=1` is set, a one-line diagnostic showing where
+/// the result came from is printed to stderr.
 pub fn terminal_size() -> Option<(Width, Height)> {
-    let size = terminal_size_using_fd();
-    match size {
-        Some((Width(0), Height(0))) => {
-            terminal_size_using_env()
-        },
-        _ => size,
+    let size = terminal_size_with_config(&DetectConfig::default());
+    if env::var_os(DEBUG_ENV).as_deref() == Some(OsStr::new("1")) {
+        debug_dump(&size);
+    }
+    size
+}
+
+/// The descriptors `DetectConfig::default()` queries: just STDOUT.
+pub const DEFAULT_FDS: &[RawFd] = &[self::libc::STDOUT_FILENO];
+
+/// Queries the descriptors in `config.fds`, falling back to the environment.
+pub fn detect(config: &DetectConfig) -> Option<(Width, Height)> {
+    let mut found_tty = false;
+    for &fd in config.fds {
+        if let Some(ws) = winsize_of(fd) {
+            if ws.ws_row != 0 || ws.ws_col != 0 {
+                return Some((Width(ws.ws_col), Height(ws.ws_row)));
+            }
+            found_tty = true;
+        }
+    }
+    if !found_tty {
+        None
+    } else if config.use_env {
+        terminal_size_using_env_vars(config.columns_var, config.lines_var)
+    } else {
+        Some((Width(0), Height(0)))
     }
 }
 
+/// Prints which source produced `size`, along with the raw winsize of STDOUT.
+fn debug_dump(size: &Option<(Width, Height)>) {
+    let fd = self::libc::STDOUT_FILENO;
+    match winsize_of(fd) {
+        Some(ws) => {
+            eprintln!(
+                "terminal_size: fd {} winsize rows={} cols={} xpixel={} ypixel={}, source={}, result={:?}",
+                fd, ws.ws_row, ws.ws_col, ws.ws_xpixel, ws.ws_ypixel, source_of(&ws, size), size
+            );
+        }
+        None => {
+            eprintln!("terminal_size: fd {} is not a tty, result={:?}", fd, size);
+        }
+    }
+}
+
+/// Returns which source `terminal_size` used to produce `size`, given the
+/// winsize of STDOUT.
+fn source_of(ws: &WinSize, size: &Option<(Width, Height)>) -> &'static str {
+    if ws.ws_row != 0 || ws.ws_col != 0 {
+        "fd"
+    } else if size.is_some() {
+        "env"
+    } else {
+        "none (tty is 0x0, env unset)"
+    }
+}
+
+/// Returns a multi-line report of everything `terminal_size` looks at.
+///
+/// The report names the backend, shows the raw winsize of each standard
+/// stream, the `COLUMNS`, `LINES` and `TERM` variables, and what
+/// `terminal_size` returns and why. It's meant to be pasted into bug reports.
+pub fn describe_environment() -> String {
+    use std::fmt::Write;
+    use self::libc::{STDERR_FILENO, STDIN_FILENO, STDOUT_FILENO};
+
+    let mut report = String::new();
+    let _ = writeln!(report, "backend: {}", BACKEND);
+    for &(fd, name) in &[(STDIN_FILENO, "stdin"), (STDOUT_FILENO, "stdout"), (STDERR_FILENO, "stderr")] {
+        let _ = match winsize_of(fd) {
+            Some(ws) => writeln!(
+                report,
+                "{} (fd {}): tty, rows={} cols={} xpixel={} ypixel={}",
+                name, fd, ws.ws_row, ws.ws_col, ws.ws_xpixel, ws.ws_ypixel
+            ),
+            None => writeln!(report, "{} (fd {}): not a tty", name, fd),
+        };
+    }
+    for var in &["COLUMNS", "LINES", "TERM"] {
+        let _ = match env::var_os(var) {
+            Some(value) => writeln!(report, "{}={:?}", var, value),
+            None => writeln!(report, "{} is not set", var),
+        };
+    }
+    let size = terminal_size();
+    let source = match winsize_of(STDOUT_FILENO) {
+        Some(ws) => source_of(&ws, &size),
+        None => "none, stdout is not a tty",
+    };
+    let _ = write!(report, "terminal_size: {:?} (source: {})", size, source);
+    report
+}
+
 /// Returns the size of the terminal using the given file descriptor, if available.
 ///
 /// If the STDOUT file descriptor is not a tty, returns `None`
 pub fn terminal_size_using_fd() -> Option<(Width, Height)> {
     use self::libc::STDOUT_FILENO;
-    use self::libc::ioctl;
+
+    winsize_of(STDOUT_FILENO).map(|ws| (Width(ws.ws_col), Height(ws.ws_row)))
+}
+
+/// Returns the full size of the terminal behind `fd`, pixels included.
+///
+/// This reads the same `TIOCGWINSZ` ioctl as `terminal_size_using_fd`, but
+/// keeps the pixel dimensions, which are 0 when the terminal doesn't report
+/// them. No environment fallback is applied. If `fd` is not a tty, returns
+/// `None`.
+pub fn terminal_size_with_pixels(fd: RawFd) -> Option<TerminalSize> {
+    winsize_of(fd).map(|ws| TerminalSize {
+        width: Width(ws.ws_col),
+        height: Height(ws.ws_row),
+        pixel_width: ws.ws_xpixel,
+        pixel_height: ws.ws_ypixel,
+    })
+}
+
+/// Returns which of STDIN, STDOUT and STDERR are ttys.
+pub fn stdio_tty_status() -> StdioTty {
+    use self::libc::{isatty, STDERR_FILENO, STDIN_FILENO, STDOUT_FILENO};
+
+    unsafe {
+        StdioTty {
+            stdin: isatty(STDIN_FILENO) == 1,
+            stdout: isatty(STDOUT_FILENO) == 1,
+            stderr: isatty(STDERR_FILENO) == 1,
+        }
+    }
+}
+
+/// Returns the size of the terminal behind `fd`, then closes it.
+///
+/// This suits code that opens a terminal (such as `/dev/tty`) just to ask
+/// for its size. If `fd` is not a tty, returns `None`.
+pub fn terminal_size_of_owned(fd: OwnedFd) -> Option<(Width, Height)> {
+    winsize_of(fd.as_raw_fd()).map(|ws| (Width(ws.ws_col), Height(ws.ws_row)))
+}
+
+/// Returns the size of the first terminal among this process's open file
+/// descriptors, as listed in `/proc/self/fd`.
+///
+/// This is a last resort for when STDOUT is a pipe or file, but some other
+/// descriptor still refers to a terminal. Descriptors are tried in
+/// ascending order, and ttys that report a size of 0x0 are skipped.
+/// Returns `None` if `/proc` is unavailable or no terminal is found.
+#[cfg(target_os = "linux")]
+pub fn terminal_size_scan_proc_fds() -> Option<(Width, Height)> {
+    let entries = fs::read_dir("/proc/self/fd").ok()?;
+    let mut fds: Vec<c_int> = entries
+        .filter_map(|entry| entry.ok()?.file_name().to_str()?.parse().ok())
+        .collect();
+    fds.sort();
+
+    fds.into_iter()
+        .filter_map(winsize_of)
+        .find(|ws| ws.ws_row != 0 || ws.ws_col != 0)
+        .map(|ws| (Width(ws.ws_col), Height(ws.ws_row)))
+}
+
+/// Returns the raw winsize of `fd`, or `None` if it is not a tty.
+fn winsize_of(fd: c_int) -> Option<WinSize> {
     use self::libc::isatty;
 
-    let fd = STDOUT_FILENO;
     let is_tty: bool = unsafe { isatty(fd) == 1 };
 
     if !is_tty {
         return None;
     }
 
-    let (rows, cols) = unsafe {
-        let mut winsize = WinSize {
-            ws_row: 0,
-            ws_col: 0,
-            ws_xpixel: 0,
-            ws_ypixel: 0,
-        };
-        ioctl(fd, TIOCGWINSZ, &mut winsize);
-        let rows = if winsize.ws_row > 0 {
-            winsize.ws_row
-        } else {
-            0
-        };
-        let cols = if winsize.ws_col > 0 {
-            winsize.ws_col
-        } else {
-            0
-        };
-        (rows as u16, cols as u16)
-    };
+    Some(normalize_winsize(read_winsize(fd)))
+}
 
-    Some((Width(cols), Height(rows)))
+/// Treats the pixel size along an axis as unknown when the cell count along
+/// it is 0.
+///
+/// Any process can set such a winsize on a pty with `TIOCSWINSZ`, and a
+/// pixel size is meaningless without the cells it is divided into.
+fn normalize_winsize(ws: WinSize) -> WinSize {
+    WinSize {
+        ws_xpixel: if ws.ws_col == 0 { 0 } else { ws.ws_xpixel },
+        ws_ypixel: if ws.ws_row == 0 { 0 } else { ws.ws_ypixel },
+        ..ws
+    }
 }
 
-/// Returns the size of the terminal using system env:
-/// COLUMNS and LINES
+/// Reads the winsize of the terminal behind `fd` with `TIOCGWINSZ`.
 ///
-/// If both env are 0, returns `None`
-fn terminal_size_using_env() -> Option<(Width, Height)> {
-    let get_u16_from_env = |x: &str| -> u16 {
-        if let Some(v) = env::var_os(x) {
-            v.into_string().unwrap()
-                .parse::<u16>().unwrap()
-        } else {
-            0
-        }
+/// All fields are 0 if the ioctl fails.
+#[cfg(not(target_os = "nto"))]
+fn read_winsize(fd: c_int) -> WinSize {
+    use self::libc::ioctl;
+
+    let mut winsize = WinSize {
+        ws_row: 0,
+        ws_col: 0,
+        ws_xpixel: 0,
+        ws_ypixel: 0,
     };
-    let c = get_u16_from_env("COLUMNS");
-    let r = get_u16_from_env("LINES");
+    unsafe {
+        ioctl(fd, TIOCGWINSZ, &mut winsize);
+    }
+    winsize
+}
 
-    if r == 0 && c == 0 {
-        None
-    } else {
-        return Some((Width(c), Height(r)))
+/// Reads the winsize of the terminal behind `fd` on QNX Neutrino.
+///
+/// QNX character devices report their size through `devctl()` with
+/// `DCMD_CHR_GETSIZE` (see `<sys/dcmd_chr.h>`), which libc wraps as
+/// `tcgetsize()` in `<termios.h>`. Only rows and columns are known, and
+/// both are 0 if the call fails, so `terminal_size` falls back to the
+/// environment.
+#[cfg(target_os = "nto")]
+fn read_winsize(fd: c_int) -> WinSize {
+    extern "C" {
+        fn tcgetsize(filedes: c_int, prows: *mut c_int, pcols: *mut c_int) -> c_int;
+    }
+
+    let mut rows: c_int = 0;
+    let mut cols: c_int = 0;
+    if unsafe { tcgetsize(fd, &mut rows, &mut cols) } != 0 {
+        rows = 0;
+        cols = 0;
+    }
+    let clamp = |v: c_int| if v > 0 { v.min(u16::MAX as c_int) as u16 } else { 0 };
+    WinSize {
+        ws_row: clamp(rows),
+        ws_col: clamp(cols),
+        ws_xpixel: 0,
+        ws_ypixel: 0,
     }
 }
 
+#[test]
+/// The environment report names the backend and every standard stream
+fn describe_environment_smoke() {
+    let report = describe_environment();
+    println!("{}", report);
+    assert!(report.contains(BACKEND));
+    assert!(report.contains("stdin (fd 0)"));
+    assert!(report.contains("stdout (fd 1)"));
+    assert!(report.contains("stderr (fd 2)"));
+    assert!(report.contains("COLUMNS"));
+    assert!(report.contains("terminal_size: "));
+}
+
+#[test]
+/// Pixels without cells along the same axis are treated as unknown
+fn normalize_winsize_drops_pixels_without_cells() {
+    let ws = normalize_winsize(WinSize {
+        ws_row: 24,
+        ws_col: 0,
+        ws_xpixel: 640,
+        ws_ypixel: 480,
+    });
+    assert_eq!((ws.ws_row, ws.ws_col, ws.ws_xpixel, ws.ws_ypixel), (24, 0, 0, 480));
+
+    let ws = normalize_winsize(WinSize {
+        ws_row: 24,
+        ws_col: 80,
+        ws_xpixel: 640,
+        ws_ypixel: 480,
+    });
+    assert_eq!((ws.ws_row, ws.ws_col, ws.ws_xpixel, ws.ws_ypixel), (24, 80, 640, 480));
+}
+
+#[test]
+/// A 0x0 tty is only blamed on the environment when that produced a size
+fn source_of_zero_sized_tty() {
+    let zero = WinSize {
+        ws_row: 0,
+        ws_col: 0,
+        ws_xpixel: 0,
+        ws_ypixel: 0,
+    };
+    assert_eq!(source_of(&zero, &Some((Width(132), Height(43)))), "env");
+    assert_eq!(source_of(&zero, &None), "none (tty is 0x0, env unset)");
+
+    let sized = WinSize {
+        ws_row: 24,
+        ws_col: 80,
+        ..zero
+    };
+    assert_eq!(source_of(&sized, &Some((Width(80), Height(24)))), "fd");
+}
+
 #[test]
 /// Compare using_fd with the output of `stty size`
 fn compare_using_fd_with_stty() {
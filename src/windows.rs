@@ -1,25 +1,151 @@
 extern crate winapi;
 
-use super::{Height, Width};
+use std::env;
+use std::ffi::OsStr;
+use std::ptr;
+
+use self::winapi::um::consoleapi::GetConsoleMode;
+use self::winapi::um::fileapi::{CreateFileW, OPEN_EXISTING};
+use self::winapi::um::handleapi::{CloseHandle, INVALID_HANDLE_VALUE};
+use self::winapi::um::processenv::GetStdHandle;
+use self::winapi::um::winbase::{STD_ERROR_HANDLE, STD_INPUT_HANDLE, STD_OUTPUT_HANDLE};
+use self::winapi::um::wincon::{
+    GetConsoleScreenBufferInfo, CONSOLE_SCREEN_BUFFER_INFO, COORD, SMALL_RECT,
+};
+use self::winapi::um::winnt::{FILE_SHARE_READ, FILE_SHARE_WRITE, GENERIC_READ, GENERIC_WRITE, HANDLE};
+
+use super::{
+    terminal_size_using_env, terminal_size_using_env_vars, terminal_size_with_config, DetectConfig, Height,
+    StdioTty, Width, DEBUG_ENV,
+};
+
+/// Describes how this backend reads the terminal size.
+const BACKEND: &str = "windows (GetConsoleScreenBufferInfo)";
 
 /// Returns the size of the terminal, if available.
 ///
 /// Note that this returns the size of the actual command window, and
 /// not the overall size of the command window buffer
+///
+/// The STDOUT handle is tried first, then STDERR. If both are redirected,
+/// the active screen buffer of the attached console is opened through
+/// `CONOUT$`, so a console is still found when only STDIN is attached to
+/// it. (`CONIN$` itself is an input buffer and has no window to measure.)
+/// Returns `None` only if no console is reachable.
+///
+/// Some hosts (such as the Visual Studio integrated terminal) briefly
+/// report a degenerate or default 80x24 window while starting up. In that
+/// case the exported `COLUMNS` and `LINES` are used instead, if set, as on
+/// Unix.
+///
+/// If `TERMINAL_SIZE_DEBUG=1` is set, a one-line diagnostic showing the
+/// raw console window is printed to stderr.
 pub fn terminal_size() -> Option<(Width, Height)> {
-    use self::winapi::um::processenv::GetStdHandle;
-    use self::winapi::um::winbase::{STD_INPUT_HANDLE, STD_OUTPUT_HANDLE};
-    use self::winapi::um::wincon::{
-        GetConsoleScreenBufferInfo, CONSOLE_SCREEN_BUFFER_INFO, COORD, SMALL_RECT,
+    let size = terminal_size_with_config(&DetectConfig::default());
+    if env::var_os(DEBUG_ENV).as_deref() == Some(OsStr::new("1")) {
+        debug_dump(&size);
+    }
+    size
+}
+
+/// Measures the console window, falling back to the variables named by
+/// `config` for placeholder sizes if `config.use_env` is set.
+pub fn detect(config: &DetectConfig) -> Option<(Width, Height)> {
+    let (_, _, csbi) = find_screen_buffer()?;
+    let size = window_size(&csbi);
+    if config.use_env {
+        Some(fallback_to_env(size, || {
+            terminal_size_using_env_vars(config.columns_var, config.lines_var)
+        }))
+    } else {
+        Some(size)
+    }
+}
+
+/// Prints which handle produced `size`, along with its raw console window.
+fn debug_dump(size: &Option<(Width, Height)>) {
+    match find_screen_buffer() {
+        Some((name, hand, csbi)) => {
+            let r = &csbi.srWindow;
+            eprintln!(
+                "terminal_size: {} handle {:?} window left={} top={} right={} bottom={}, result={:?}",
+                name, hand, r.Left, r.Top, r.Right, r.Bottom, size
+            );
+        }
+        None => {
+            eprintln!("terminal_size: no console screen buffer is reachable, result={:?}", size);
+        }
+    }
+}
+
+/// Returns which of the standard handles are attached to a console.
+pub fn stdio_tty_status() -> StdioTty {
+    let is_console = |std_handle| {
+        let mut mode = 0;
+        unsafe { GetConsoleMode(GetStdHandle(std_handle), &mut mode) != 0 }
     };
-    use self::winapi::um::winnt::HANDLE;
+    StdioTty {
+        stdin: is_console(STD_INPUT_HANDLE),
+        stdout: is_console(STD_OUTPUT_HANDLE),
+        stderr: is_console(STD_ERROR_HANDLE),
+    }
+}
+
+/// Finds a console screen buffer to measure, trying STDOUT, STDERR and then
+/// `CONOUT$`.
+///
+/// Returns the name of the handle that worked along with the handle itself,
+/// which for `CONOUT$` has already been closed and is only good for display.
+fn find_screen_buffer() -> Option<(&'static str, HANDLE, CONSOLE_SCREEN_BUFFER_INFO)> {
+    for &(std_handle, name) in &[(STD_OUTPUT_HANDLE, "stdout"), (STD_ERROR_HANDLE, "stderr")] {
+        let hand: HANDLE = unsafe { GetStdHandle(std_handle) };
+        if let Some(csbi) = screen_buffer_info(hand) {
+            return Some((name, hand, csbi));
+        }
+    }
+    let conout = open_conout()?;
+    screen_buffer_info(conout.0).map(|csbi| ("CONOUT$", conout.0, csbi))
+}
 
-    let hand: HANDLE = unsafe { GetStdHandle(STD_OUTPUT_HANDLE) };
+/// A handle that is closed when dropped.
+struct OwnedHandle(HANDLE);
+
+impl Drop for OwnedHandle {
+    fn drop(&mut self) {
+        unsafe {
+            CloseHandle(self.0);
+        }
+    }
+}
+
+/// Opens the active screen buffer of the attached console, if there is one.
+fn open_conout() -> Option<OwnedHandle> {
+    let name: Vec<u16> = "CONOUT$\0".encode_utf16().collect();
+    let hand: HANDLE = unsafe {
+        CreateFileW(
+            name.as_ptr(),
+            GENERIC_READ | GENERIC_WRITE,
+            FILE_SHARE_READ | FILE_SHARE_WRITE,
+            ptr::null_mut(),
+            OPEN_EXISTING,
+            0,
+            ptr::null_mut(),
+        )
+    };
+    if hand == INVALID_HANDLE_VALUE {
+        None
+    } else {
+        Some(OwnedHandle(hand))
+    }
+}
 
+/// Returns the screen buffer info of the console behind `hand`, or `None`
+/// if it isn't a console screen buffer.
+fn screen_buffer_info(hand: HANDLE) -> Option<CONSOLE_SCREEN_BUFFER_INFO> {
     let zc = COORD { X: 0, Y: 0 };
     let mut csbi = CONSOLE_SCREEN_BUFFER_INFO {
-        dwSize: zc.clone(),
-        dwCursorPosition: zc.clone(),
+        dwSize: zc,
+        dwCursorPosition: zc,
         wAttributes: 0,
         srWindow: SMALL_RECT {
             Left: 0,
@@ -31,10 +157,168 @@ pub fn terminal_size() -> Option<(Width, Height)> {
     };
     let success: bool = unsafe { GetConsoleScreenBufferInfo(hand, &mut csbi) != 0 };
     if success {
-        let w: Width = Width((csbi.srWindow.Right - csbi.srWindow.Left + 1) as u16);
-        let h: Height = Height((csbi.srWindow.Bottom - csbi.srWindow.Top + 1) as u16);
-        Some((w, h))
+        Some(csbi)
     } else {
         None
     }
 }
+
+/// Returns the size of the visible window of a console screen buffer.
+fn window_size(csbi: &CONSOLE_SCREEN_BUFFER_INFO) -> (Width, Height) {
+    let w: Width = Width((csbi.srWindow.Right - csbi.srWindow.Left + 1) as u16);
+    let h: Height = Height((csbi.srWindow.Bottom - csbi.srWindow.Top + 1) as u16);
+    (w, h)
+}
+
+/// Returns a multi-line report of everything `terminal_size` looks at.
+///
+/// The report names the backend, shows the console window behind each
+/// standard handle and `CONOUT$`, the `COLUMNS`, `LINES` and `TERM`
+/// variables, and what `terminal_size` returns and why. It's meant to be
+/// pasted into bug reports.
+pub fn describe_environment() -> String {
+    use std::fmt::Write;
+
+    let mut report = String::new();
+    let _ = writeln!(report, "backend: {}", BACKEND);
+    for &(std_handle, name) in &[
+        (STD_INPUT_HANDLE, "stdin"),
+        (STD_OUTPUT_HANDLE, "stdout"),
+        (STD_ERROR_HANDLE, "stderr"),
+    ] {
+        let hand: HANDLE = unsafe { GetStdHandle(std_handle) };
+        let _ = match screen_buffer_info(hand) {
+            Some(csbi) => {
+                let r = &csbi.srWindow;
+                writeln!(
+                    report,
+                    "{} (handle {:?}): console, window left={} top={} right={} bottom={}",
+                    name, hand, r.Left, r.Top, r.Right, r.Bottom
+                )
+            }
+            None => writeln!(report, "{} (handle {:?}): not a console screen buffer", name, hand),
+        };
+    }
+    let _ = match open_conout().and_then(|conout| screen_buffer_info(conout.0)) {
+        Some(csbi) => {
+            let r = &csbi.srWindow;
+            writeln!(
+                report,
+                "CONOUT$: console, window left={} top={} right={} bottom={}",
+                r.Left, r.Top, r.Right, r.Bottom
+            )
+        }
+        None => writeln!(report, "CONOUT$: no console attached"),
+    };
+    for var in &["COLUMNS", "LINES", "TERM"] {
+        let _ = match env::var_os(var) {
+            Some(value) => writeln!(report, "{}={:?}", var, value),
+            None => writeln!(report, "{} is not set", var),
+        };
+    }
+    let source = match find_screen_buffer() {
+        Some((name, _, csbi)) => {
+            let window = window_size(&csbi);
+            if fallback_to_env(window, terminal_size_using_env) == window {
+                name
+            } else {
+                "env"
+            }
+        }
+        None => "none, no console is reachable",
+    };
+    let _ = write!(report, "terminal_size: {:?} (source: {})", terminal_size(), source);
+    report
+}
+
+/// Returns `size`, or the size from `env` if `size` looks like a placeholder.
+///
+/// `env` is only consulted when the console reports a zero dimension, or
+/// exactly the default 80x24.
+fn fallback_to_env<F>(size: (Width, Height), env: F) -> (Width, Height)
+where
+    F: FnOnce() -> Option<(Width, Height)>,
+{
+    let (Width(w), Height(h)) = size;
+    let placeholder = w == 0 || h == 0 || (w == 80 && h == 24);
+    if placeholder {
+        env().unwrap_or(size)
+    } else {
+        size
+    }
+}
+
+#[test]
+/// The environment report names the backend and every standard handle
+fn describe_environment_smoke() {
+    let report = describe_environment();
+    println!("{}", report);
+    assert!(report.contains(BACKEND));
+    assert!(report.contains("stdin (handle"));
+    assert!(report.contains("stdout (handle"));
+    assert!(report.contains("stderr (handle"));
+    assert!(report.contains("terminal_size: "));
+}
+
+#[test]
+#[ignore]
+/// Not a real test: run as a child process by `terminal_size_with_stdout_redirected`
+fn child_compares_with_conout() {
+    use self::winapi::um::wincon::GetConsoleWindow;
+
+    // Only run when started by the test below, not by `cargo test -- --ignored`.
+    if env::var_os("TERMINAL_SIZE_TEST_CHILD").is_none() {
+        return;
+    }
+    // Without a console, there's nothing to find.
+    if unsafe { GetConsoleWindow() }.is_null() {
+        return;
+    }
+    assert!(terminal_size().is_some());
+}
+
+#[test]
+/// With STDOUT and STDERR redirected, the console is still found through `CONOUT$`
+fn terminal_size_with_stdout_redirected() {
+    use std::process::{Command, Stdio};
+
+    let output = Command::new(env::current_exe().unwrap())
+        .args([
+            "--exact",
+            "windows::child_compares_with_conout",
+            "--ignored",
+            "--nocapture",
+        ])
+        .env("TERMINAL_SIZE_TEST_CHILD", "1")
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .output()
+        .unwrap();
+    assert!(
+        output.status.success(),
+        "{}{}",
+        String::from_utf8_lossy(&output.stdout),
+        String::from_utf8_lossy(&output.stderr)
+    );
+}
+
+#[test]
+/// Placeholder console sizes fall back to the environment
+fn fallback_to_env_replaces_placeholders() {
+    let env = || Some((Width(120), Height(40)));
+    let no_env = || None;
+
+    assert_eq!(fallback_to_env((Width(80), Height(24)), env), (Width(120), Height(40)));
+    assert_eq!(fallback_to_env((Width(0), Height(0)), env), (Width(120), Height(40)));
+    assert_eq!(fallback_to_env((Width(100), Height(0)), env), (Width(120), Height(40)));
+    assert_eq!(fallback_to_env((Width(80), Height(24)), no_env), (Width(80), Height(24)));
+}
+
+#[test]
+/// Real console sizes are never overridden by the environment
+fn fallback_to_env_keeps_real_sizes() {
+    let env = || Some((Width(120), Height(40)));
+
+    assert_eq!(fallback_to_env((Width(100), Height(30)), env), (Width(100), Height(30)));
+    assert_eq!(fallback_to_env((Width(80), Height(25)), env), (Width(80), Height(25)));
+}
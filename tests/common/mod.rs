@@ -0,0 +1,104 @@
+//! Helpers shared by the integration tests.
+//!
+//! Most of the interesting behaviour of this crate only shows up when talking
+//! to a real terminal, so these helpers hand out pseudo-terminals with a known
+//! size instead of relying on whatever the test runner happens to be attached to.
+
+#![allow(dead_code)]
+
+use std::env;
+use std::fs::File;
+use std::os::unix::io::{AsRawFd, FromRawFd};
+use std::process::Command;
+use std::ptr;
+use std::sync::Mutex;
+
+use libc;
+
+/// Both ends of a freshly opened pseudo-terminal.
+pub struct Pty {
+    pub master: File,
+    pub slave: File,
+}
+
+/// Opens a pseudo-terminal whose window size is `cols` x `rows`.
+pub fn open_pty(cols: u16, rows: u16) -> Pty {
+    let winsize = libc::winsize {
+        ws_row: rows,
+        ws_col: cols,
+        ws_xpixel: 0,
+        ws_ypixel: 0,
+    };
+    let mut master = -1;
+    let mut slave = -1;
+    let ret = unsafe {
+        libc::openpty(
+            &mut master,
+            &mut slave,
+            ptr::null_mut(),
+            ptr::null(),
+            &winsize,
+        )
+    };
+    assert_eq!(ret, 0, "openpty failed");
+    unsafe {
+        Pty {
+            master: File::from_raw_fd(master),
+            slave: File::from_raw_fd(slave),
+        }
+    }
+}
+
+/// Changes the window size of the terminal behind `file`.
+pub fn resize(file: &File, cols: u16, rows: u16) {
+    resize_pixels(file, cols, rows, 0, 0);
+}
+
+/// Changes the window size of the terminal behind `file`, pixels included.
+pub fn resize_pixels(file: &File, cols: u16, rows: u16, xpixel: u16, ypixel: u16) {
+    let winsize = libc::winsize {
+        ws_row: rows,
+        ws_col: cols,
+        ws_xpixel: xpixel,
+        ws_ypixel: ypixel,
+    };
+    let ret = unsafe { libc::ioctl(file.as_raw_fd(), libc::TIOCSWINSZ, &winsize) };
+    assert_eq!(ret, 0, "TIOCSWINSZ failed");
+}
+
+static STDOUT_LOCK: Mutex<()> = Mutex::new(());
+
+/// Runs `f` with STDOUT temporarily pointing at `file`.
+pub fn with_stdout<T, F: FnOnce() -> T>(file: &File, f: F) -> T {
+    let _guard = STDOUT_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    unsafe {
+        let saved = libc::dup(libc::STDOUT_FILENO);
+        assert!(saved >= 0, "dup failed");
+        assert!(libc::dup2(file.as_raw_fd(), libc::STDOUT_FILENO) >= 0);
+        let result = f();
+        libc::dup2(saved, libc::STDOUT_FILENO);
+        libc::close(saved);
+        result
+    }
+}
+
+/// Set in the environment of the child processes started by `run_self`.
+pub const CHILD_ENV: &str = "TERMINAL_SIZE_TEST_CHILD";
+
+/// Returns a command that re-runs this test binary to run just the
+/// `#[ignore]`d test `name` as a child process.
+///
+/// Such child tests make assertions about the environment the parent set
+/// up for them, so they should return early unless `is_child()`.
+pub fn run_self(name: &str) -> Command {
+    let mut cmd = Command::new(env::current_exe().unwrap());
+    cmd.args(["--exact", name, "--ignored", "--nocapture"])
+        .env(CHILD_ENV, "1");
+    cmd
+}
+
+/// Returns `true` in a child process started by `run_self`, rather than in
+/// a plain `cargo test -- --ignored` run.
+pub fn is_child() -> bool {
+    env::var_os(CHILD_ENV).is_some()
+}
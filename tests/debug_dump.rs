@@ -0,0 +1,55 @@
+#![cfg(unix)]
+
+extern crate libc;
+extern crate terminal_size;
+
+mod common;
+
+use std::process::{Output, Stdio};
+
+use terminal_size::terminal_size;
+
+#[test]
+#[ignore]
+/// Not a real test: run as a child process by `debug_dump_goes_to_stderr`
+fn child_queries_size() {
+    if !common::is_child() {
+        return;
+    }
+    terminal_size();
+}
+
+/// Runs `child_queries_size` in a child process whose STDOUT is a 132x43 pty.
+fn run_child(debug: Option<&str>) -> Output {
+    let pty = common::open_pty(132, 43);
+    let mut cmd = common::run_self("child_queries_size");
+    cmd.env_remove("TERMINAL_SIZE_DEBUG")
+        .stdout(Stdio::from(pty.slave))
+        .stderr(Stdio::piped());
+    if let Some(value) = debug {
+        cmd.env("TERMINAL_SIZE_DEBUG", value);
+    }
+    let output = cmd.output().unwrap();
+    assert!(output.status.success());
+    output
+}
+
+#[test]
+/// `TERMINAL_SIZE_DEBUG=1` prints the raw winsize and source to stderr
+fn debug_dump_goes_to_stderr() {
+    let output = run_child(Some("1"));
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    println!("{}", stderr);
+    assert!(stderr.contains("terminal_size: fd 1 winsize rows=43 cols=132"));
+    assert!(stderr.contains("source=fd"));
+}
+
+#[test]
+/// Nothing is printed unless the variable is set to `1`
+fn no_debug_dump_by_default() {
+    for debug in &[None, Some("0")] {
+        let output = run_child(*debug);
+        let stderr = String::from_utf8(output.stderr).unwrap();
+        assert!(!stderr.contains("terminal_size:"), "{}", stderr);
+    }
+}
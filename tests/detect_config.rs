@@ -0,0 +1,102 @@
+#![cfg(unix)]
+
+extern crate libc;
+extern crate terminal_size;
+
+mod common;
+
+use std::env;
+use std::os::unix::io::AsRawFd;
+
+use terminal_size::{terminal_size_with_config, DetectConfig, Height, TerminalSize, Width};
+
+#[test]
+/// The first descriptor that is a tty with a size wins
+fn probes_fds_in_order() {
+    let small = common::open_pty(40, 10);
+    let large = common::open_pty(200, 60);
+    let unset = common::open_pty(0, 0);
+    let fds = [-1, unset.slave.as_raw_fd(), large.slave.as_raw_fd(), small.slave.as_raw_fd()];
+    let config = DetectConfig {
+        fds: &fds,
+        ..DetectConfig::default()
+    };
+    assert_eq!(terminal_size_with_config(&config), Some((Width(200), Height(60))));
+
+    let config = DetectConfig {
+        fds: &[-1],
+        ..DetectConfig::default()
+    };
+    assert_eq!(terminal_size_with_config(&config), None);
+}
+
+#[test]
+/// A 0x0 tty falls back to the configured variables, unless that's turned off
+fn env_fallback_uses_configured_names() {
+    env::set_var("TERMINAL_SIZE_TEST_COLS", "132");
+    env::set_var("TERMINAL_SIZE_TEST_ROWS", "43");
+    let unset = common::open_pty(0, 0);
+    let fds = [unset.slave.as_raw_fd()];
+    let config = DetectConfig {
+        fds: &fds,
+        columns_var: "TERMINAL_SIZE_TEST_COLS",
+        lines_var: "TERMINAL_SIZE_TEST_ROWS",
+        ..DetectConfig::default()
+    };
+    assert_eq!(terminal_size_with_config(&config), Some((Width(132), Height(43))));
+
+    let config = DetectConfig {
+        use_env: false,
+        ..config
+    };
+    assert_eq!(terminal_size_with_config(&config), Some((Width(0), Height(0))));
+}
+
+#[test]
+/// A valid override variable beats the tty, and invalid or zero ones are ignored
+fn override_var_takes_precedence() {
+    env::set_var("TERMINAL_SIZE_TEST_OVERRIDE", "100x30");
+    let pty = common::open_pty(80, 24);
+    let fds = [pty.slave.as_raw_fd()];
+    let config = DetectConfig {
+        fds: &fds,
+        override_var: Some("TERMINAL_SIZE_TEST_OVERRIDE"),
+        ..DetectConfig::default()
+    };
+    assert_eq!(terminal_size_with_config(&config), Some((Width(100), Height(30))));
+
+    for value in &["100 by 30", "0x0", "0x24", "80x0"] {
+        env::set_var("TERMINAL_SIZE_TEST_BAD_OVERRIDE", value);
+        let config = DetectConfig {
+            override_var: Some("TERMINAL_SIZE_TEST_BAD_OVERRIDE"),
+            ..config
+        };
+        assert_eq!(terminal_size_with_config(&config), Some((Width(80), Height(24))), "{}", value);
+    }
+}
+
+#[test]
+/// The result is clamped to the configured bounds
+fn clamps_result() {
+    let pty = common::open_pty(300, 5);
+    let fds = [pty.slave.as_raw_fd()];
+    let config = DetectConfig {
+        fds: &fds,
+        clamp: Some((TerminalSize::new(40, 10), TerminalSize::new(200, 50))),
+        ..DetectConfig::default()
+    };
+    assert_eq!(terminal_size_with_config(&config), Some((Width(200), Height(10))));
+}
+
+#[test]
+/// Bounds that cross on an axis leave that axis unclamped
+fn crossed_clamp_bounds_are_ignored() {
+    let pty = common::open_pty(300, 5);
+    let fds = [pty.slave.as_raw_fd()];
+    let config = DetectConfig {
+        fds: &fds,
+        clamp: Some((TerminalSize::new(40, 50), TerminalSize::new(200, 10))),
+        ..DetectConfig::default()
+    };
+    assert_eq!(terminal_size_with_config(&config), Some((Width(200), Height(5))));
+}
@@ -0,0 +1,59 @@
+#![cfg(unix)]
+
+extern crate libc;
+extern crate terminal_size;
+
+mod common;
+
+use std::process::{Command, Stdio};
+
+use terminal_size::terminal_size;
+
+#[test]
+#[ignore]
+/// Not a real test: run as a child process by the tests below
+fn child_reports_size() {
+    if !common::is_child() {
+        return;
+    }
+    eprintln!("size={:?}", terminal_size());
+}
+
+/// Runs `child_reports_size` via `sh -c "<script>"`, with STDOUT on a pty
+/// reporting 0x0 so that `terminal_size` falls back to the environment.
+fn run_through_shell(script: &str) -> String {
+    let pty = common::open_pty(0, 0);
+    let child = common::run_self("child_reports_size");
+    let output = Command::new("sh")
+        .arg("-c")
+        .arg(format!("{}; exec \"$0\" \"$@\"", script))
+        .arg(child.get_program())
+        .args(child.get_args())
+        .env(common::CHILD_ENV, "1")
+        .env_remove("COLUMNS")
+        .env_remove("LINES")
+        .stdout(Stdio::from(pty.slave))
+        .stderr(Stdio::piped())
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    String::from_utf8(output.stderr).unwrap()
+}
+
+#[test]
+/// Shell-local `COLUMNS`/`LINES` never reach the process, so they're ignored
+fn unexported_variables_are_invisible() {
+    let stderr = run_through_shell("COLUMNS=123; LINES=45");
+    assert!(stderr.contains("size=None"), "{}", stderr);
+}
+
+#[test]
+/// Exported `COLUMNS`/`LINES` are used when the tty reports 0x0
+fn exported_variables_are_used() {
+    let stderr = run_through_shell("COLUMNS=123; LINES=45; export COLUMNS LINES");
+    assert!(
+        stderr.contains("size=Some((Width(123), Height(45)))"),
+        "{}",
+        stderr
+    );
+}
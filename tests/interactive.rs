@@ -0,0 +1,189 @@
+#![cfg(unix)]
+
+extern crate libc;
+extern crate terminal_size;
+
+mod common;
+
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Write};
+use std::mem;
+use std::os::unix::io::AsRawFd;
+use std::os::unix::process::CommandExt;
+use std::process::{Output, Stdio};
+use std::thread;
+use std::time::Duration;
+
+use terminal_size::{terminal_size_interactive, TerminalSize};
+
+#[test]
+#[ignore]
+/// Not a real test: run as a child process by the tests below
+fn child_queries_interactively() {
+    if !common::is_child() {
+        return;
+    }
+    let timeout = Duration::from_millis(500);
+    eprintln!("size={:?}", terminal_size_interactive(timeout));
+}
+
+/// How long `child_queries_late` waits for an answer.
+const LATE_TIMEOUT: Duration = Duration::from_millis(200);
+
+/// How long the terminal takes to answer in `late_reply_is_discarded`: a
+/// little past the timeout.
+const LATE_DELAY: Duration = Duration::from_millis(230);
+
+#[test]
+#[ignore]
+/// Not a real test: run as a child process by `late_reply_is_discarded`
+fn child_queries_late() {
+    if !common::is_child() {
+        return;
+    }
+    eprintln!("size={:?}", terminal_size_interactive(LATE_TIMEOUT));
+
+    // Read whatever input a program running after us would see.
+    let tty = OpenOptions::new().read(true).write(true).open("/dev/tty").unwrap();
+    let mut leftover = Vec::new();
+    unsafe {
+        let mut raw: libc::termios = mem::zeroed();
+        assert_eq!(libc::tcgetattr(tty.as_raw_fd(), &mut raw), 0);
+        libc::cfmakeraw(&mut raw);
+        assert_eq!(libc::tcsetattr(tty.as_raw_fd(), libc::TCSANOW, &raw), 0);
+        let mut pollfd = libc::pollfd {
+            fd: tty.as_raw_fd(),
+            events: libc::POLLIN,
+            revents: 0,
+        };
+        let mut buf = [0u8; 64];
+        while libc::poll(&mut pollfd, 1, 200) > 0 {
+            let n = libc::read(tty.as_raw_fd(), buf.as_mut_ptr() as *mut libc::c_void, buf.len());
+            if n <= 0 {
+                break;
+            }
+            leftover.extend_from_slice(&buf[..n as usize]);
+        }
+    }
+    eprintln!("leftover={:?}", String::from_utf8_lossy(&leftover));
+}
+
+/// Runs the test `child` in a new session whose controlling terminal is
+/// `pty`, so that its `/dev/tty` is the pty. The terminal answers with
+/// `replies`, `delay` after it has seen both queries.
+fn run_child(pty: common::Pty, child: &str, replies: Option<&'static [u8]>, delay: Duration) -> Output {
+    let slave = pty.slave.try_clone().unwrap();
+    let mut master = pty.master;
+
+    let terminal = thread::spawn(move || {
+        // Play the terminal: wait for both queries, then answer them.
+        let mut seen = Vec::new();
+        let mut buf = [0u8; 64];
+        while !seen.windows(5).any(|w| w == b"\x1b[18t") {
+            match master.read(&mut buf) {
+                Ok(n) if n > 0 => seen.extend_from_slice(&buf[..n]),
+                _ => return seen,
+            }
+        }
+        thread::sleep(delay);
+        if let Some(replies) = replies {
+            master.write_all(replies).unwrap();
+        }
+        // Keep the master open until the child is done with the pty.
+        let _ = master.read(&mut buf);
+        seen
+    });
+
+    let mut cmd = common::run_self(child);
+    cmd.stdin(Stdio::from(slave))
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+    unsafe {
+        cmd.pre_exec(|| {
+            if libc::setsid() < 0 || libc::ioctl(0, libc::TIOCSCTTY, 0) < 0 {
+                return Err(std::io::Error::last_os_error());
+            }
+            Ok(())
+        });
+    }
+    let output = cmd.output().unwrap();
+    drop(pty.slave);
+    drop(terminal);
+    output
+}
+
+fn termios_of(file: &File) -> libc::termios {
+    unsafe {
+        let mut termios: libc::termios = mem::zeroed();
+        assert_eq!(libc::tcgetattr(file.as_raw_fd(), &mut termios), 0);
+        termios
+    }
+}
+
+fn same_termios(a: &libc::termios, b: &libc::termios) -> bool {
+    a.c_iflag == b.c_iflag && a.c_oflag == b.c_oflag && a.c_cflag == b.c_cflag && a.c_lflag == b.c_lflag
+}
+
+#[test]
+/// A cooperating terminal's cell and pixel reports make up the full size
+fn cooperating_terminal_reports_size() {
+    let pty = common::open_pty(0, 0);
+    let slave = pty.slave.try_clone().unwrap();
+    let before = termios_of(&slave);
+
+    let output = run_child(
+        pty,
+        "child_queries_interactively",
+        Some(b"\x1b[4;480;640t\x1b[8;24;80t"),
+        Duration::ZERO,
+    );
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(output.status.success(), "{}", stderr);
+
+    let expected = TerminalSize {
+        pixel_width: 640,
+        pixel_height: 480,
+        ..TerminalSize::new(80, 24)
+    };
+    assert!(stderr.contains(&format!("size={:?}", Some(expected))), "{}", stderr);
+    assert!(same_termios(&before, &termios_of(&slave)), "termios was not restored");
+}
+
+#[test]
+/// Without pixel support, the size comes back with unknown pixels
+fn cells_only_terminal_reports_size() {
+    let pty = common::open_pty(0, 0);
+    let output = run_child(pty, "child_queries_interactively", Some(b"\x1b[8;50;132t"), Duration::ZERO);
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(output.status.success(), "{}", stderr);
+    assert!(
+        stderr.contains(&format!("size={:?}", Some(TerminalSize::new(132, 50)))),
+        "{}",
+        stderr
+    );
+}
+
+#[test]
+/// A terminal that never answers times out, and its settings are restored
+fn silent_terminal_times_out() {
+    let pty = common::open_pty(0, 0);
+    let slave = pty.slave.try_clone().unwrap();
+    let before = termios_of(&slave);
+
+    let output = run_child(pty, "child_queries_interactively", None, Duration::ZERO);
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(output.status.success(), "{}", stderr);
+    assert!(stderr.contains("size=None"), "{}", stderr);
+    assert!(same_termios(&before, &termios_of(&slave)), "termios was not restored");
+}
+
+#[test]
+/// A reply that arrives just after the timeout is not left behind as input
+fn late_reply_is_discarded() {
+    let pty = common::open_pty(0, 0);
+    let output = run_child(pty, "child_queries_late", Some(b"\x1b[4;480;640t\x1b[8;24;80t"), LATE_DELAY);
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(output.status.success(), "{}", stderr);
+    assert!(stderr.contains("size=None"), "{}", stderr);
+    assert!(stderr.contains("leftover=\"\""), "{}", stderr);
+}
@@ -0,0 +1,37 @@
+#![cfg(unix)]
+
+extern crate libc;
+extern crate terminal_size;
+
+mod common;
+
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+
+use terminal_size::{terminal_size, Height, Width};
+
+/// How long `terminal_size()` may take before we consider it blocked.
+///
+/// A single ioctl finishes in microseconds; the slack only covers spawning the
+/// thread on a busy machine.
+const DEADLINE: Duration = Duration::from_millis(100);
+
+#[test]
+/// `terminal_size()` must answer straight from the ioctl, never by waiting on the terminal
+fn terminal_size_does_not_block() {
+    let pty = common::open_pty(100, 40);
+
+    let result = common::with_stdout(&pty.slave, || {
+        let (tx, rx) = mpsc::channel();
+        thread::spawn(move || {
+            let _ = tx.send(terminal_size());
+        });
+        rx.recv_timeout(DEADLINE)
+    });
+
+    match result {
+        Ok(size) => assert_eq!(size, Some((Width(100), Height(40)))),
+        Err(_) => panic!("terminal_size() did not return within {:?}", DEADLINE),
+    }
+}
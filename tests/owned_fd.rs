@@ -0,0 +1,56 @@
+#![cfg(unix)]
+
+extern crate libc;
+extern crate terminal_size;
+
+mod common;
+
+use std::fs::OpenOptions;
+use std::os::unix::io::{AsRawFd, OwnedFd};
+use std::sync::Mutex;
+
+use terminal_size::{terminal_size_of_owned, Height, Width};
+
+/// Keeps the tests from reusing each other's fd numbers while one of them
+/// checks that its fd was closed.
+static FD_LOCK: Mutex<()> = Mutex::new(());
+
+#[test]
+/// Passing an `OwnedFd` by value measures the terminal and closes the fd
+fn owned_fd_is_measured_and_closed() {
+    let _guard = FD_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let pty = common::open_pty(90, 33);
+    let fd = OwnedFd::from(pty.slave);
+    let raw = fd.as_raw_fd();
+
+    assert_eq!(terminal_size_of_owned(fd), Some((Width(90), Height(33))));
+    assert_eq!(unsafe { libc::fcntl(raw, libc::F_GETFD) }, -1);
+}
+
+#[test]
+/// `/dev/tty` is the usual source of an owned terminal fd, when there is one
+fn owned_dev_tty() {
+    let _guard = FD_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    if let Ok(tty) = OpenOptions::new().read(true).write(true).open("/dev/tty") {
+        assert!(terminal_size_of_owned(OwnedFd::from(tty)).is_some());
+    }
+}
+
+#[test]
+/// A file that isn't a terminal gives `None`
+fn owned_non_tty_is_none() {
+    let _guard = FD_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let file = OpenOptions::new().read(true).open("/dev/null").unwrap();
+    assert_eq!(terminal_size_of_owned(OwnedFd::from(file)), None);
+}
+
+#[test]
+/// A pty reporting pixels but no columns is measured without panicking
+fn pixels_without_cells_do_not_panic() {
+    let _guard = FD_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let pty = common::open_pty(0, 24);
+    common::resize_pixels(&pty.slave, 0, 24, 640, 480);
+
+    let fd = OwnedFd::from(pty.slave);
+    assert_eq!(terminal_size_of_owned(fd), Some((Width(0), Height(24))));
+}
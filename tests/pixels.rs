@@ -0,0 +1,41 @@
+#![cfg(unix)]
+
+extern crate libc;
+extern crate terminal_size;
+
+mod common;
+
+use std::os::unix::io::AsRawFd;
+
+use terminal_size::{terminal_size_with_pixels, TerminalSize};
+
+#[test]
+/// The pixel dimensions reported by the pty are kept
+fn pixels_come_from_the_ioctl() {
+    let pty = common::open_pty(80, 24);
+    common::resize_pixels(&pty.slave, 80, 24, 800, 480);
+
+    let size = terminal_size_with_pixels(pty.slave.as_raw_fd()).unwrap();
+    let expected = TerminalSize {
+        pixel_width: 800,
+        pixel_height: 480,
+        ..TerminalSize::new(80, 24)
+    };
+    assert_eq!(size, expected);
+    assert_eq!(size.scale_to_fit_pixels((1600, 800)), Some((800, 400)));
+}
+
+#[test]
+/// Terminals that don't report pixels give a size that can't scale images
+fn unknown_pixels_are_zero() {
+    let pty = common::open_pty(80, 24);
+    let size = terminal_size_with_pixels(pty.slave.as_raw_fd()).unwrap();
+    assert_eq!(size, TerminalSize::new(80, 24));
+    assert_eq!(size.scale_to_fit_pixels((1600, 800)), None);
+}
+
+#[test]
+/// A descriptor that isn't a tty gives `None`
+fn non_tty_is_none() {
+    assert_eq!(terminal_size_with_pixels(-1), None);
+}
@@ -0,0 +1,40 @@
+#![cfg(unix)]
+
+extern crate libc;
+extern crate terminal_size;
+
+mod common;
+
+use std::time::Duration;
+
+use terminal_size::{current_wrap_width, resize_channel, Height, Width};
+
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+#[test]
+/// Raising SIGWINCH delivers the new size to every live channel
+fn sigwinch_delivers_new_size() {
+    let pty = common::open_pty(80, 24);
+
+    common::with_stdout(&pty.slave, || {
+        let dropped = resize_channel();
+        let rx = resize_channel();
+        drop(dropped);
+
+        common::resize(&pty.slave, 120, 50);
+        unsafe {
+            libc::raise(libc::SIGWINCH);
+        }
+        assert_eq!(rx.recv_timeout(TIMEOUT), Ok((Width(120), Height(50))));
+        assert_eq!(current_wrap_width(0), 120);
+
+        // A second channel opened later sees only later resizes.
+        let late = resize_channel();
+        common::resize(&pty.slave, 100, 30);
+        unsafe {
+            libc::raise(libc::SIGWINCH);
+        }
+        assert_eq!(rx.recv_timeout(TIMEOUT), Ok((Width(100), Height(30))));
+        assert_eq!(late.recv_timeout(TIMEOUT), Ok((Width(100), Height(30))));
+    });
+}
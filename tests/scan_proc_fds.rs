@@ -0,0 +1,39 @@
+#![cfg(target_os = "linux")]
+
+extern crate libc;
+extern crate terminal_size;
+
+mod common;
+
+use std::process::Stdio;
+
+use terminal_size::{terminal_size, terminal_size_scan_proc_fds, Height, Width};
+
+#[test]
+#[ignore]
+/// Not a real test: run as a child process by `scan_finds_pty_with_stdout_redirected`
+fn child_scans_proc_fds() {
+    if !common::is_child() {
+        return;
+    }
+    let _pty = common::open_pty(77, 11);
+    assert_eq!(terminal_size(), None);
+    assert_eq!(terminal_size_scan_proc_fds(), Some((Width(77), Height(11))));
+}
+
+#[test]
+/// With every standard stream redirected, the scan still finds an open pty
+fn scan_finds_pty_with_stdout_redirected() {
+    let output = common::run_self("child_scans_proc_fds")
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .output()
+        .unwrap();
+    assert!(
+        output.status.success(),
+        "{}{}",
+        String::from_utf8_lossy(&output.stdout),
+        String::from_utf8_lossy(&output.stderr)
+    );
+}
@@ -0,0 +1,59 @@
+#![cfg(unix)]
+
+extern crate libc;
+extern crate terminal_size;
+
+mod common;
+
+use std::process::Stdio;
+
+use terminal_size::{stdio_tty_status, StdioTty};
+
+#[test]
+#[ignore]
+/// Not a real test: run as a child process by the tests below
+fn child_reports_tty_status() {
+    if !common::is_child() {
+        return;
+    }
+    eprintln!("status={:?}", stdio_tty_status());
+}
+
+/// Runs `child_reports_tty_status` with the given STDIN and STDOUT, and
+/// STDERR piped back to us.
+fn run_child(stdin: Stdio, stdout: Stdio) -> String {
+    let output = common::run_self("child_reports_tty_status")
+        .stdin(stdin)
+        .stdout(stdout)
+        .stderr(Stdio::piped())
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    String::from_utf8(output.stderr).unwrap()
+}
+
+#[test]
+/// Only STDIN is a tty when it alone is attached to a pty
+fn only_stdin_is_tty() {
+    let pty = common::open_pty(80, 24);
+    let stderr = run_child(Stdio::from(pty.slave), Stdio::piped());
+    let expected = StdioTty {
+        stdin: true,
+        stdout: false,
+        stderr: false,
+    };
+    assert!(stderr.contains(&format!("status={:?}", expected)), "{}", stderr);
+}
+
+#[test]
+/// Only STDOUT is a tty when it alone is attached to a pty
+fn only_stdout_is_tty() {
+    let pty = common::open_pty(80, 24);
+    let stderr = run_child(Stdio::null(), Stdio::from(pty.slave));
+    let expected = StdioTty {
+        stdin: false,
+        stdout: true,
+        stderr: false,
+    };
+    assert!(stderr.contains(&format!("status={:?}", expected)), "{}", stderr);
+}